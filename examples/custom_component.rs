@@ -0,0 +1,62 @@
+//! A binary with a component of its own, alongside the built-in ones
+//!
+//! Run it with a configuration using the component:
+//! ```kdl
+//! components {
+//!     greeting "world"
+//! }
+//! ```
+
+use serde_json::json;
+use std::io::{self, Write};
+use std::process::ExitCode;
+
+use rust_motd::async_trait::async_trait;
+use rust_motd::component::{BoxedCollected, Collected, Component};
+use rust_motd::config::global_config::GlobalConfig;
+use rust_motd::config::registry::Registry;
+use rust_motd::default_prepare;
+
+/// Prints `Hello, <name>!`
+#[derive(knus::Decode, Debug)]
+struct Greeting {
+    #[knus(argument)]
+    name: String,
+}
+
+#[async_trait]
+impl Component for Greeting {
+    async fn collect(self: Box<Self>, _global_config: &GlobalConfig) -> BoxedCollected {
+        Box::new(CollectedGreeting { name: self.name })
+    }
+    fn name(&self) -> &'static str {
+        "greeting"
+    }
+    default_prepare!();
+}
+
+struct CollectedGreeting {
+    name: String,
+}
+
+impl Collected for CollectedGreeting {
+    fn print(
+        self: Box<Self>,
+        _global_config: &GlobalConfig,
+        _width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        writeln!(out, "Hello, {}!", self.name)?;
+        writeln!(out)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({ "name": self.name })
+    }
+}
+
+fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let mut registry = Registry::new();
+    registry.register::<Greeting>("greeting");
+    rust_motd::cli::main(&registry)
+}
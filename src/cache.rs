@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::component::{Collected, Issue, PerfData};
+use crate::config::global_config::GlobalConfig;
+
+/// What is stored on disk for a component with a `cache-ttl`
+/// Both the printed text (with colours) and the JSON data are kept,
+/// so that a cached component can be used with any `--format`
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    /// Unix timestamp of when the data was collected
+    created: u64,
+    text: String,
+    data: serde_json::Value,
+    #[serde(default)]
+    issues: Vec<Issue>,
+    #[serde(default)]
+    perfdata: Vec<PerfData>,
+    #[serde(default)]
+    min_width: Option<usize>,
+    #[serde(default)]
+    width: Option<usize>,
+}
+
+/// Output of a component, either read from the cache or about to be written to it
+/// The cache is read before the components are prepared, so the minimum width of the component
+/// is kept along with the width the text was printed with
+pub struct CachedCollected {
+    pub text: String,
+    pub data: serde_json::Value,
+    pub issues: Vec<Issue>,
+    pub perfdata: Vec<PerfData>,
+    pub min_width: Option<usize>,
+    pub width: Option<usize>,
+}
+
+impl Collected for CachedCollected {
+    fn print(
+        self: Box<Self>,
+        _global_config: &GlobalConfig,
+        _width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        out.write_all(self.text.as_bytes())
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        self.data.clone()
+    }
+
+    fn issues(&self) -> Vec<Issue> {
+        self.issues.clone()
+    }
+
+    fn perfdata(&self) -> Vec<PerfData> {
+        self.perfdata.clone()
+    }
+}
+
+/// Directory where the output of components with a `cache-ttl` is stored
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Use the configured `cache-dir`, or `$XDG_CACHE_HOME/rust-motd` (`~/.cache/rust-motd`)
+    /// Returns `None` if neither is set and `$HOME` is not set either
+    pub fn new(global_config: &GlobalConfig) -> Option<Self> {
+        let dir = match &global_config.cache_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => env::var("XDG_CACHE_HOME")
+                .map(PathBuf::from)
+                .or_else(|_| env::var("HOME").map(|home| PathBuf::from(home).join(".cache")))
+                .ok()?
+                .join("rust-motd"),
+        };
+        Some(Cache { dir })
+    }
+
+    fn path(&self, name: &str, key: u64) -> PathBuf {
+        self.dir.join(format!("{name}-{key:016x}.json"))
+    }
+
+    /// Read the output of a component, if it was stored less than `ttl` seconds ago
+    /// A missing or unreadable entry is not an error, the component is simply collected again
+    pub fn load(&self, name: &str, key: u64, ttl: u64) -> Option<CachedCollected> {
+        let entry: CacheEntry =
+            serde_json::from_str(&fs::read_to_string(self.path(name, key)).ok()?).ok()?;
+        if now().saturating_sub(entry.created) >= ttl {
+            return None;
+        }
+        Some(CachedCollected {
+            text: entry.text,
+            data: entry.data,
+            issues: entry.issues,
+            perfdata: entry.perfdata,
+            min_width: entry.min_width,
+            width: entry.width,
+        })
+    }
+
+    /// Store the output of a component
+    /// The entry is written to a temporary file and renamed,
+    /// so that concurrent logins never read a partially written entry
+    pub fn store(&self, name: &str, key: u64, collected: &CachedCollected) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let entry = CacheEntry {
+            created: now(),
+            text: collected.text.clone(),
+            data: collected.data.clone(),
+            issues: collected.issues.clone(),
+            perfdata: collected.perfdata.clone(),
+            min_width: collected.min_width,
+            width: collected.width,
+        };
+        let path = self.path(name, key);
+        let temp_path = path.with_extension(format!("tmp.{}", std::process::id()));
+        fs::write(&temp_path, serde_json::to_vec(&entry)?)?;
+        fs::rename(&temp_path, &path)
+    }
+}
+
+/// Key identifying the output of a component
+/// It changes whenever anything that affects the output changes: the configuration of the component
+/// (see `ConfiguredComponent::kdl`), the global configuration, or the system root
+/// The width is not part of the key since it is only known once the components are prepared,
+/// it is checked against the width of the cached text instead
+pub fn cache_key(kdl: &str, global_config: &GlobalConfig) -> u64 {
+    let sysroot = global_config.sysroot.path("/");
+    let sysroot = sysroot.to_string_lossy();
+    // FNV-1a, which unlike the hashers of the standard library gives the same key in every version
+    [kdl, &global_config.kdl, &sysroot]
+        .iter()
+        .flat_map(|part| part.bytes().chain([0]))
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        })
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::Severity;
+
+    #[test]
+    fn test_store_and_load() {
+        let global_config = GlobalConfig {
+            cache_dir: Some(
+                env::temp_dir()
+                    .join(format!("rust-motd-test-{}", std::process::id()))
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+            ..GlobalConfig::default()
+        };
+        let cache = Cache::new(&global_config).unwrap();
+        let collected = CachedCollected {
+            text: "cached\n".to_string(),
+            data: serde_json::json!({ "value": 1 }),
+            issues: vec![Issue::new(Severity::Warn, "warning".to_string())],
+            perfdata: vec![],
+            min_width: Some(40),
+            width: Some(60),
+        };
+
+        assert!(cache.load("test", 1, 60).is_none());
+        cache.store("test", 1, &collected).unwrap();
+        let loaded = cache.load("test", 1, 60).unwrap();
+        assert_eq!(loaded.text, collected.text);
+        assert_eq!(loaded.data, collected.data);
+        assert_eq!(loaded.severity(), Severity::Warn);
+        assert_eq!((loaded.min_width, loaded.width), (Some(40), Some(60)));
+        // Expired and different keys are misses
+        assert!(cache.load("test", 1, 0).is_none());
+        assert!(cache.load("test", 2, 60).is_none());
+
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+}
@@ -0,0 +1,67 @@
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::component::{ConfiguredComponent, Selection};
+use crate::config::get_config::get_config;
+use crate::config::registry::Registry;
+use crate::sysroot::SysRoot;
+
+/// A problem with the configuration of a component
+#[derive(Error, Debug, Diagnostic)]
+#[error("{component}: {message}")]
+struct Problem {
+    component: &'static str,
+    message: String,
+}
+
+/// All of the problems found by `rust-motd check`
+#[derive(Error, Debug, Diagnostic)]
+#[error("Found {} problem(s) in the configuration", problems.len())]
+struct CheckError {
+    #[related]
+    problems: Vec<Problem>,
+}
+
+/// Parse the configuration and check every component (including the ones disabled by `when`),
+/// without printing the MOTD
+/// Returns whether the configuration is valid
+pub async fn check(
+    config_path: Option<String>,
+    registry: &Registry,
+    sysroot: SysRoot,
+    selection: &Selection,
+) -> bool {
+    let mut config = match get_config(config_path, registry, &sysroot) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Config Error: {:?}", miette::Report::new(e));
+            return false;
+        }
+    };
+
+    config.global.sysroot = sysroot;
+    selection.apply(&mut config.components);
+
+    let mut problems = vec![];
+    for ConfiguredComponent { component, .. } in config.components {
+        let name = component.name();
+        problems.extend(
+            component
+                .check(&config.global)
+                .await
+                .into_iter()
+                .map(|message| Problem {
+                    component: name,
+                    message,
+                }),
+        );
+    }
+
+    if problems.is_empty() {
+        println!("The configuration is valid.");
+        true
+    } else {
+        eprintln!("{:?}", miette::Report::new(CheckError { problems }));
+        false
+    }
+}
@@ -0,0 +1,158 @@
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tokio::runtime::Runtime;
+
+use crate::check::check;
+use crate::component::Selection;
+use crate::config::migrate::migrate_toml;
+use crate::config::registry::Registry;
+use crate::render::{run, Format, RunOptions};
+use crate::sysroot::SysRoot;
+use crate::timings::TimingsFormat;
+
+#[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Args {
+    config_path: Option<String>,
+
+    /// How to print the components [default: text]
+    /// (`status` has its own format)
+    #[arg(long, value_enum, global = true)]
+    format: Option<Format>,
+
+    /// Also print the full diagnostics of the errors of the components to stderr
+    #[arg(long, short, global = true)]
+    verbose: bool,
+
+    /// Read the state of the system (`/proc`, `/sys`, `/var/log/wtmp`...) from this directory,
+    /// like the root of the host mounted in a container
+    #[arg(long, global = true, env = "RUST_MOTD_SYSROOT", default_value = "/")]
+    sysroot: PathBuf,
+
+    /// Report the time spent by each component to stderr, as a table or as a line of JSON
+    #[arg(
+        long,
+        alias = "profile",
+        global = true,
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "table",
+        require_equals = true
+    )]
+    timings: Option<TimingsFormat>,
+
+    /// Only run the components with these names or ids, like `--only memory,filesystems`
+    #[arg(long, global = true, value_delimiter = ',', value_name = "COMPONENTS")]
+    only: Vec<String>,
+
+    /// Do not run the components with these names or ids, like `--skip weather`
+    #[arg(long, global = true, value_delimiter = ',', value_name = "COMPONENTS")]
+    skip: Vec<String>,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Write the MOTD to a file instead of printing it
+    ///
+    /// Meant to be run periodically (e.g. from a systemd timer),
+    /// so that the file can be shown instantly on login (e.g. by `pam_motd`)
+    Render {
+        config_path: Option<String>,
+
+        /// The file to write, which is replaced atomically
+        #[arg(long, short)]
+        output: PathBuf,
+    },
+    /// Check the configuration without printing the MOTD
+    ///
+    /// Reports all of the problems found (like missing certificates or mount points)
+    /// and exits with a non-zero status if there are any
+    Check { config_path: Option<String> },
+    /// Print the health of the components as a monitoring plugin (Nagios, Icinga...)
+    ///
+    /// Prints a one-line summary with performance data,
+    /// and exits with 0 (OK), 1 (WARNING), 2 (CRITICAL) or 3 (UNKNOWN)
+    Status { config_path: Option<String> },
+    /// Convert a legacy TOML configuration to the KDL format
+    ///
+    /// The KDL configuration is printed, e.g. `rust-motd migrate config.toml > config.kdl`
+    Migrate { config_path: PathBuf },
+}
+
+/// Parse the command line and run the requested command
+/// The components of the registry can be used in the configuration, along with the built-in ones
+pub fn main(registry: &Registry) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    // The runtime is built by hand rather than with `#[tokio::main]`, because dropping it waits
+    // for all blocking tasks, including the components that timed out (which may never finish)
+    let runtime = Runtime::new()?;
+    let args = Args::parse();
+    if matches!(args.command, Some(Commands::Status { .. })) && args.format.is_some() {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "the argument '--format' cannot be used with 'status', which has its own format",
+            )
+            .exit();
+    }
+    let sysroot = SysRoot::new(args.sysroot);
+    let options = RunOptions {
+        selection: Selection {
+            only: args.only,
+            skip: args.skip,
+        },
+        format: args.format.unwrap_or_default(),
+        output: None,
+        verbose: args.verbose,
+        timings: args.timings,
+    };
+    let result = match args.command {
+        Some(Commands::Render {
+            config_path,
+            output,
+        }) => {
+            let options = RunOptions {
+                output: Some(output),
+                ..options
+            };
+            runtime.block_on(run(config_path, registry, sysroot, &options))
+        }
+        Some(Commands::Check { config_path }) => Ok(
+            if runtime.block_on(check(config_path, registry, sysroot, &options.selection)) {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            },
+        ),
+        Some(Commands::Status { config_path }) => {
+            let options = RunOptions {
+                format: Format::Status,
+                ..options
+            };
+            runtime.block_on(run(config_path, registry, sysroot, &options))
+        }
+        Some(Commands::Migrate { config_path }) => migrate(&config_path),
+        None => runtime.block_on(run(args.config_path, registry, sysroot, &options)),
+    };
+    runtime.shutdown_background();
+    result
+}
+
+/// Print the KDL equivalent of a legacy TOML configuration
+fn migrate(config_path: &Path) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    match migrate_toml(&fs::read_to_string(config_path)?) {
+        Ok(kdl) => {
+            print!("{kdl}");
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(e) => {
+            eprintln!("Config Error: {e}");
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
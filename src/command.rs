@@ -1,7 +1,9 @@
 use std::ffi::OsStr;
-use std::io::ErrorKind;
-use std::process::{Command, Output};
+use std::io::{ErrorKind, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Output, Stdio};
 use thiserror::Error;
+use tokio::io::AsyncWriteExt;
 
 #[derive(Error, Debug)]
 pub enum BetterCommandError {
@@ -22,6 +24,8 @@ pub enum BetterCommandError {
 pub struct BetterCommand {
     executable: String,
     command: Command,
+    /// Written to the standard input of the command, which gets no input otherwise
+    stdin: Option<Vec<u8>>,
 }
 
 fn u8vec_to_string(s: Vec<u8>) -> String {
@@ -33,6 +37,7 @@ impl BetterCommand {
         BetterCommand {
             executable: executable.to_string(),
             command: Command::new(executable),
+            stdin: None,
         }
     }
 
@@ -50,13 +55,33 @@ impl BetterCommand {
         self
     }
 
+    pub fn stdin(&mut self, input: impl Into<Vec<u8>>) -> &mut BetterCommand {
+        self.stdin = Some(input.into());
+        self
+    }
+
     pub fn output(&mut self) -> Result<Output, BetterCommandError> {
-        self.command.output().map_err(|err| match err.kind() {
-            ErrorKind::NotFound => BetterCommandError::NotFound {
-                executable: self.executable.clone(),
-            },
-            _ => BetterCommandError::IOError { source: err },
-        })
+        let output = match &self.stdin {
+            None => self.command.output(),
+            Some(input) => self
+                .command
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .and_then(|mut child| {
+                    // The input is written before reading the output, so it must fit in the pipe
+                    // (which is plenty for the small inputs this is used for)
+                    // A command that exits without reading its input is not an error
+                    let mut stdin = child.stdin.take().unwrap();
+                    match stdin.write_all(input) {
+                        Err(err) if err.kind() != ErrorKind::BrokenPipe => return Err(err),
+                        _ => drop(stdin),
+                    }
+                    child.wait_with_output()
+                }),
+        };
+        output.map_err(|err| spawn_error(&self.executable, err))
     }
 
     pub fn get_output_string(&mut self) -> Result<String, BetterCommandError> {
@@ -65,14 +90,75 @@ impl BetterCommand {
 
     pub fn check_status_and_get_output_string(&mut self) -> Result<String, BetterCommandError> {
         let output = self.output()?;
+        check_status(&self.executable, output)
+    }
+
+    /// Like `check_status_and_get_output_string`, but without blocking the thread
+    /// The command is killed if the future is dropped before the command exits,
+    /// like when the component running it times out
+    /// This includes the processes it started (like the commands run by `sh -c`),
+    /// since it runs in its own process group
+    pub async fn check_status_and_get_output_string_async(
+        mut self,
+    ) -> Result<String, BetterCommandError> {
+        self.command.process_group(0);
+        let mut command = tokio::process::Command::from(self.command);
+        command
+            .kill_on_drop(true)
+            .stdin(match self.stdin {
+                Some(_) => Stdio::piped(),
+                None => Stdio::null(),
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let output = async {
+            let mut child = command.spawn()?;
+            let mut group = KillGroupOnDrop(child.id());
+            if let Some(input) = &self.stdin {
+                let mut stdin = child.stdin.take().unwrap();
+                match stdin.write_all(input).await {
+                    Err(err) if err.kind() != ErrorKind::BrokenPipe => return Err(err),
+                    _ => drop(stdin),
+                }
+            }
+            let output = child.wait_with_output().await;
+            // Processes the command left running on purpose are not killed
+            group.0 = None;
+            output
+        }
+        .await;
+        let output = output.map_err(|err| spawn_error(&self.executable, err))?;
+        check_status(&self.executable, output)
+    }
+}
+
+/// Kills a process group when dropped, unless its ID was taken out
+struct KillGroupOnDrop(Option<u32>);
 
-        match output.status.success() {
-            true => Ok(u8vec_to_string(output.stdout)),
-            false => Err(BetterCommandError::ExitStatusError {
-                executable: self.executable.clone(),
-                exit_code: output.status.code().unwrap(),
-                error: u8vec_to_string(output.stderr),
-            }),
+impl Drop for KillGroupOnDrop {
+    fn drop(&mut self) {
+        if let Some(id) = self.0 {
+            unsafe { libc::kill(-(id as libc::pid_t), libc::SIGKILL) };
         }
     }
 }
+
+fn spawn_error(executable: &str, err: std::io::Error) -> BetterCommandError {
+    match err.kind() {
+        ErrorKind::NotFound => BetterCommandError::NotFound {
+            executable: executable.to_string(),
+        },
+        _ => BetterCommandError::IOError { source: err },
+    }
+}
+
+fn check_status(executable: &str, output: Output) -> Result<String, BetterCommandError> {
+    match output.status.success() {
+        true => Ok(u8vec_to_string(output.stdout)),
+        false => Err(BetterCommandError::ExitStatusError {
+            executable: executable.to_string(),
+            exit_code: output.status.code().unwrap(),
+            error: u8vec_to_string(output.stderr),
+        }),
+    }
+}
@@ -1,10 +1,24 @@
-use crate::config::global_config::GlobalConfig;
+use crate::config::global_config::{ErrorPolicy, GlobalConfig};
+use crate::config::theme::Level;
+use crate::config::when::When;
+use crate::layout::Placement;
 use async_trait::async_trait;
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+use termion::style;
+use thiserror::Error;
+use unicode_ellipsis::truncate_str;
 
 /// Boxed component with all other traits
-// Send is required because print is async
+// Send is required because collect is async and runs on another thread
 pub type BoxedComponent = Box<dyn Component + Send>;
 
+/// Boxed result of the collect phase
+pub type BoxedCollected = Box<dyn Collected + Send>;
+
 /// Return type for the prepare phase
 pub type PrepareReturn = Option<(BoxedComponent, Option<Constraints>)>;
 
@@ -13,14 +27,85 @@ pub type PrepareReturn = Option<(BoxedComponent, Option<Constraints>)>;
 /// For example, the `Filesystems` component has a minimum width based on the length in characters
 /// of its mount points and other data, and this width is used by the memory component
 /// (aligning the progress bars like this is aesthetically pleasing)
+/// The width of the terminal is a maximum width (whatever the `progress-width`),
+/// and components given less than their minimum width shrink or truncate their content
 pub struct Constraints {
     pub min_width: Option<usize>,
 }
 
+/// Options accepted by every component, in addition to the component's own options
+#[derive(Debug, Default, Clone)]
+pub struct ComponentOptions {
+    /// A name for the component, to tell it apart from other components of the same kind
+    /// (with `--only`, `--skip`, `hide` and `system`)
+    pub id: Option<String>,
+    /// Maximum time in seconds the component may take to prepare and collect its data
+    /// Overrides the global `timeout`
+    pub timeout: Option<u64>,
+    /// Reuse the output of the component for this many seconds instead of collecting it again
+    pub cache_ttl: Option<u64>,
+    /// The component is only printed if all of these conditions match
+    pub when: Vec<When>,
+}
+
+impl ComponentOptions {
+    /// Whether the `when` conditions of the component match
+    pub fn is_enabled(&self) -> bool {
+        self.when.iter().all(When::matches)
+    }
+}
+
+/// A component along with its generic options, as read from the configuration file
+pub struct ConfiguredComponent {
+    pub component: BoxedComponent,
+    pub options: ComponentOptions,
+    /// The configuration of the component as KDL (without the generic options),
+    /// which identifies its output in the cache
+    pub kdl: String,
+    /// The position of the component in the configuration, starting at 1
+    /// (it is kept when other components are left out by `--only`, `--skip` or `when`)
+    pub position: usize,
+    /// Where the component is printed if it is inside of a `row`
+    pub placement: Option<Placement>,
+}
+
+impl ConfiguredComponent {
+    /// Whether the component has this name (like `docker`) or this `id`
+    pub fn is_named(&self, name: &str) -> bool {
+        self.component.name() == name || self.options.id.as_deref() == Some(name)
+    }
+}
+
+/// The components to run, chosen on the command line with `--only` and `--skip`
+/// Components are selected by their name (like `docker`) or their `id`
+#[derive(Debug, Default, Clone)]
+pub struct Selection {
+    pub only: Vec<String>,
+    pub skip: Vec<String>,
+}
+
+impl Selection {
+    pub fn selects(&self, component: &ConfiguredComponent) -> bool {
+        (self.only.is_empty() || self.only.iter().any(|name| component.is_named(name)))
+            && !self.skip.iter().any(|name| component.is_named(name))
+    }
+
+    /// Keep only the selected components
+    /// The names that match no component (likely typos) are reported to stderr
+    pub fn apply(&self, components: &mut Vec<ConfiguredComponent>) {
+        for name in self.only.iter().chain(&self.skip) {
+            if !components.iter().any(|component| component.is_named(name)) {
+                eprintln!("Warning: No component is named `{name}` or has this id");
+            }
+        }
+        components.retain(|component| self.selects(component));
+    }
+}
+
 /// This trait should be implemented for all components
 /// (component being all the things the motd can print like command, memory, etc.).
 #[async_trait]
-pub trait Component {
+pub trait Component: std::fmt::Debug {
     /// Prepare the component and return its sizing constraints
     /// This also returns another Component, which allows a new struct implementing this trait to be
     /// returned with all of the prepared data
@@ -30,8 +115,291 @@ pub trait Component {
     /// Otherwise, simply return `self` if there is no data to save from the preparation phase
     fn prepare(self: Box<Self>, _global_config: &GlobalConfig) -> PrepareReturn;
 
-    /// Print the component to stdout
-    async fn print(self: Box<Self>, global_config: &GlobalConfig, width: Option<usize>);
+    /// The name of the component, as written in the configuration file (e.g. `load-avg`)
+    fn name(&self) -> &'static str;
+
+    /// Gather all of the data needed to print the component
+    /// The components are collected concurrently, so this is where anything slow belongs
+    /// (running commands, HTTP requests, talking to the Docker daemon, etc.)
+    /// The returned data is printed afterwards, in the order of the configuration file
+    async fn collect(self: Box<Self>, global_config: &GlobalConfig) -> BoxedCollected;
+
+    /// Check the configuration of the component without printing it (used by `rust-motd check`)
+    /// Returns a description of each problem, like a file that does not exist,
+    /// so that all of the problems can be reported at once
+    async fn check(self: Box<Self>, _global_config: &GlobalConfig) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// The data gathered by a component during the collect phase
+/// This should do no I/O other than printing, everything slow belongs in `Component::collect`
+pub trait Collected {
+    /// Print the component to `out`
+    /// Components never write to stdout directly, so that the output can be sent to a terminal,
+    /// a file or an in-memory buffer (for example, to test the output of a component)
+    fn print(
+        self: Box<Self>,
+        global_config: &GlobalConfig,
+        width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()>;
+
+    /// The collected data in a machine-readable form (used by `--format json`)
+    /// This should contain the raw values (bytes, ratios, timestamps) rather than the formatted
+    /// strings that are printed
+    fn to_json(&self) -> serde_json::Value;
+
+    /// The error of a component that failed to gather its data
+    /// Failures are not cached, so that the component is tried again on the next run
+    fn error(&self) -> Option<&ComponentError> {
+        None
+    }
+
+    /// What is wrong with the collected data, like a filesystem that is almost full
+    /// This should match the colours the component is printed in,
+    /// and the messages should make sense on their own (they are printed by `summary`)
+    fn issues(&self) -> Vec<Issue> {
+        vec![]
+    }
+
+    /// How healthy the collected data is (used by `rust-motd status` and `--format json`)
+    fn severity(&self) -> Severity {
+        self.issues()
+            .iter()
+            .map(|issue| issue.severity)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Performance data for monitoring (used by `rust-motd status`)
+    fn perfdata(&self) -> Vec<PerfData> {
+        vec![]
+    }
+
+    /// Receive the issues of all the components, once they have all been collected
+    /// This is only used by the `summary` component
+    fn summarize(&mut self, _issues: &[Issue]) {}
+}
+
+/// A problem found by a component, like `/var 91% full`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Issue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Issue {
+    pub fn new(severity: Severity, message: String) -> Self {
+        Issue { severity, message }
+    }
+}
+
+/// The health of a component, from best to worst, like the states of a Nagios plugin
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    #[default]
+    Ok,
+    Warn,
+    Critical,
+    /// The component failed to gather its data
+    Unknown,
+}
+
+impl Severity {
+    /// The name of the state in the output of a Nagios plugin
+    pub fn plugin_state(self) -> &'static str {
+        match self {
+            Severity::Ok => "OK",
+            Severity::Warn => "WARNING",
+            Severity::Critical => "CRITICAL",
+            Severity::Unknown => "UNKNOWN",
+        }
+    }
+
+    /// The level whose colour the severity is printed in
+    /// Unknown (a failure) is printed like critical
+    pub fn level(self) -> Level {
+        match self {
+            Severity::Ok => Level::Ok,
+            Severity::Warn => Level::Warn,
+            Severity::Critical | Severity::Unknown => Level::Critical,
+        }
+    }
+
+    /// The exit code of a Nagios plugin
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Severity::Ok => 0,
+            Severity::Warn => 1,
+            Severity::Critical => 2,
+            Severity::Unknown => 3,
+        }
+    }
+}
+
+impl From<Level> for Severity {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Ok => Severity::Ok,
+            Level::Warn => Severity::Warn,
+            Level::Critical => Severity::Critical,
+        }
+    }
+}
+
+/// A value measured by a component, with its thresholds, for monitoring systems
+/// Printed in the format of Nagios plugins: `'label'=value[unit];[warn];[crit];[min];[max]`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PerfData {
+    pub label: String,
+    pub value: f64,
+    /// `%`, `B`, `s`, or empty
+    pub unit: String,
+    pub warn: Option<f64>,
+    pub critical: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl PerfData {
+    /// The used bytes of something with a capacity (like a filesystem), with thresholds
+    /// given as percentages of the capacity like those of the progress bars
+    pub fn usage(label: &str, used: u64, total: u64, warn: usize, critical: usize) -> Self {
+        let threshold = |percent: usize| (total as f64 * percent as f64 / 100.0).round();
+        PerfData {
+            label: label.to_string(),
+            value: used as f64,
+            unit: "B".to_string(),
+            warn: Some(threshold(warn)),
+            critical: Some(threshold(critical)),
+            min: Some(0.0),
+            max: Some(total as f64),
+        }
+    }
+}
+
+impl Display for PerfData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let optional =
+            |value: Option<f64>| value.map(|value| value.to_string()).unwrap_or_default();
+        write!(
+            f,
+            "'{}'={}{};{};{};{};{}",
+            self.label.replace('\'', "''"),
+            self.value,
+            self.unit,
+            optional(self.warn),
+            optional(self.critical),
+            optional(self.min),
+            optional(self.max),
+        )
+    }
+}
+
+/// An error of a component, either from its prepare or from its collect phase
+/// How it is printed depends on the global `on-error` policy
+#[derive(Debug, Error, Diagnostic)]
+#[error("{component} error")]
+pub struct ComponentError {
+    /// The name of the component, as written in the configuration file
+    pub component: &'static str,
+    /// The heading of the component, printed above the error with `on-error "inline"`
+    pub title: Option<String>,
+    pub source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl ComponentError {
+    pub fn new(
+        component: &'static str,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Self {
+        ComponentError {
+            component,
+            title: None,
+            source: source.into(),
+        }
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// A one-line description of the error, like `uptime error: No such file or directory`
+    pub fn message(&self) -> String {
+        format!("{self}: {}", self.source)
+    }
+}
+
+/// Returned from the collect phase when a component failed to gather its data
+pub struct CollectedError(pub ComponentError);
+
+impl Collected for CollectedError {
+    fn print(
+        self: Box<Self>,
+        global_config: &GlobalConfig,
+        width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        match global_config.on_error {
+            ErrorPolicy::Inline => {
+                if let Some(title) = &self.0.title {
+                    writeln!(out, "{title}")?;
+                }
+                writeln!(out, "{}", self.0.message())?;
+            }
+            ErrorPolicy::Compact => {
+                let message = self.0.message();
+                let message = match width {
+                    Some(width) => truncate_str(&message, width),
+                    None => message.as_str().into(),
+                };
+                writeln!(
+                    out,
+                    "{}{message}{}",
+                    global_config.theme.level(Level::Critical),
+                    style::Reset
+                )?;
+            }
+            // The footer is printed once all the components have been printed
+            ErrorPolicy::Hide | ErrorPolicy::Footer => return Ok(()),
+        }
+        writeln!(out)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({ "error": self.0.message() })
+    }
+
+    fn error(&self) -> Option<&ComponentError> {
+        Some(&self.0)
+    }
+
+    fn issues(&self) -> Vec<Issue> {
+        vec![Issue::new(Severity::Unknown, self.0.message())]
+    }
+}
+
+/// A component whose prepare phase failed, which is collected into its error
+/// so that errors of the prepare phase are reported like any other error
+#[derive(Debug)]
+pub struct FailedComponent(pub ComponentError);
+
+#[async_trait]
+impl Component for FailedComponent {
+    fn prepare(self: Box<Self>, _global_config: &GlobalConfig) -> PrepareReturn {
+        Some((self, None))
+    }
+
+    fn name(&self) -> &'static str {
+        self.0.component
+    }
+
+    async fn collect(self: Box<Self>, _global_config: &GlobalConfig) -> BoxedCollected {
+        Box::new(CollectedError(self.0))
+    }
 }
 
 /// Implement the default prepare method which returns the component unmodified and no constraints
@@ -61,3 +429,99 @@ macro_rules! default_prepare {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_perfdata() {
+        let usage = PerfData::usage("/home", 750, 1000, 75, 95);
+        assert_eq!(usage.to_string(), "'/home'=750B;750;950;0;1000");
+
+        let load = PerfData {
+            label: "it's".to_string(),
+            value: 0.5,
+            unit: String::new(),
+            warn: None,
+            critical: Some(4.0),
+            min: None,
+            max: None,
+        };
+        assert_eq!(load.to_string(), "'it''s'=0.5;;4;;");
+
+        assert!(Severity::Unknown > Severity::Critical);
+        assert_eq!(Severity::from(Level::Warn).exit_code(), 1);
+    }
+
+    #[test]
+    fn test_error_policy() {
+        let print = |on_error| {
+            let global_config = GlobalConfig {
+                on_error,
+                ..GlobalConfig::default()
+            };
+            let error = ComponentError::new("docker", "connection refused").with_title("Docker:");
+            let mut out = vec![];
+            let mut writer = crate::output::StripColors::new(&mut out);
+            Box::new(CollectedError(error))
+                .print(&global_config, Some(20), &mut writer)
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(
+            print(ErrorPolicy::Inline),
+            "Docker:\ndocker error: connection refused\n\n"
+        );
+        assert_eq!(print(ErrorPolicy::Compact), "docker error: conne…\n\n");
+        assert_eq!(print(ErrorPolicy::Footer), "");
+    }
+
+    #[test]
+    fn test_selection() {
+        let config_str = r#"
+            global { version "1.0"; }
+            components {
+                uptime
+                docker id="docker-prod" socket="unix:///run/docker.sock"
+                docker id="podman" socket="unix:///run/podman/podman.sock"
+                weather loc="Toronto"
+            }
+        "#;
+        let select = |only: &[&str], skip: &[&str]| {
+            let mut config = crate::config::kdl_config::parse_kdl(
+                std::path::Path::new("test.kdl"),
+                config_str,
+                &crate::config::registry::Registry::new(),
+                None,
+            )
+            .unwrap();
+            let selection = Selection {
+                only: only.iter().map(|name| name.to_string()).collect(),
+                skip: skip.iter().map(|name| name.to_string()).collect(),
+            };
+            selection.apply(&mut config.components);
+            config
+                .components
+                .iter()
+                .map(|configured| {
+                    let id = configured.options.id.as_deref();
+                    let name = id.unwrap_or(configured.component.name());
+                    format!("{}:{name}", configured.position)
+                })
+                .collect::<Vec<_>>()
+        };
+        // The components keep their position in the configuration
+        assert_eq!(
+            select(&[], &[]),
+            ["1:uptime", "2:docker-prod", "3:podman", "4:weather"]
+        );
+        assert_eq!(select(&["docker"], &[]), ["2:docker-prod", "3:podman"]);
+        assert_eq!(select(&["podman", "uptime"], &[]), ["1:uptime", "3:podman"]);
+        assert_eq!(select(&["docker"], &["podman"]), ["2:docker-prod"]);
+        assert_eq!(
+            select(&[], &["weather", "typo"]),
+            ["1:uptime", "2:docker-prod", "3:podman"]
+        );
+    }
+}
@@ -1,26 +1,32 @@
 use lazy_static::lazy_static;
-use libc::passwd as c_passwd;
-use libc::uid_t;
 use std::collections::HashMap;
-use std::ffi::{CStr, CString};
+use std::ffi::CString;
 use std::fs::{self, File};
-use std::io::{self, BufRead, BufReader};
-use std::mem;
+use std::io::{self, BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
-use std::ptr;
-use std::thread::available_parallelism;
 use std::time::{Duration, SystemTime, SystemTimeError};
 
 use async_trait::async_trait;
 use itertools::Itertools;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use termion::{color, style};
+use serde_json::json;
+use termion::style;
 use thiserror::Error;
+use unicode_ellipsis::truncate_str;
 
-use crate::component::{Component, Constraints, PrepareReturn};
+use crate::component::{
+    BoxedCollected, Collected, Component, ComponentError, Constraints, FailedComponent, Issue,
+    PrepareReturn, Severity,
+};
 use crate::config::global_config::GlobalConfig;
+use crate::config::theme::{default_critical, default_warn, Level, DEFAULT_CRITICAL, DEFAULT_WARN};
 use crate::constants::INDENT_WIDTH;
+use crate::progress_bar::ProgressBar;
+use crate::sysroot::SysRoot;
+
+/// The narrowest the bars get, names are truncated on terminals that are too narrow
+const MIN_BAR_WIDTH: usize = "[=========]".len();
 
 /// A container for component configuration from the configuration
 /// file as well as data prepared for printing.
@@ -32,12 +38,20 @@ pub struct CgStats {
     /// List only Cgroups with higher CPU usage (0.01 ~ 1%)
     #[knus(property)]
     threshold: f64,
+    /// Percentage of CPU usage above which the bars are printed in the warn colour
+    #[knus(property, default = DEFAULT_WARN)]
+    #[serde(default = "default_warn")]
+    warn: usize,
+    /// Percentage of CPU usage above which the bars are printed in the critical colour
+    #[knus(property, default = DEFAULT_CRITICAL)]
+    #[serde(default = "default_critical")]
+    critical: usize,
 
     #[serde(skip)]
     prepared: Option<PreparedCgStats>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 struct PreparedStat {
     name: String,
     load: f64, // CPU load [0, 1]
@@ -57,7 +71,7 @@ impl Component for CgStats {
         match self.prepare_or_error(global_config) {
             Ok(prepared) => {
                 let min_width =
-                    INDENT_WIDTH + prepared.max_name_width + "100%".len() + "[=========]".len() + 2 /* spaces */;
+                    INDENT_WIDTH + prepared.max_name_width + "100%".len() + MIN_BAR_WIDTH + 2 /* spaces */;
                 self.prepared = Some(prepared);
                 Some((
                     self,
@@ -66,18 +80,56 @@ impl Component for CgStats {
                     }),
                 ))
             }
-            Err(e) => {
-                eprintln!("Cgroup Statistics error: {e}");
-                None
-            }
+            Err(err) => Some((
+                Box::new(FailedComponent(ComponentError::new("cg-stats", err))),
+                None,
+            )),
         }
     }
 
-    async fn print(self: Box<Self>, global_config: &GlobalConfig, width: Option<usize>) {
+    fn name(&self) -> &'static str {
+        "cg-stats"
+    }
+
+    async fn check(self: Box<Self>, _global_config: &GlobalConfig) -> Vec<String> {
+        let path = Path::new(&self.state_file);
+        // The state file does not exist before the first run, then its directory must be writable
+        let writable = if path.exists() {
+            File::options().append(true).open(path).is_ok()
+        } else {
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            CString::new(dir.as_os_str().as_encoded_bytes())
+                .is_ok_and(|dir| unsafe { libc::access(dir.as_ptr(), libc::W_OK) } == 0)
+        };
+        let mut problems = vec![];
+        if !writable {
+            problems.push(format!("State file {} is not writable", self.state_file));
+        }
+        problems.extend(Level::check_thresholds(self.warn, self.critical));
+        problems
+    }
+
+    /// The statistics are already read in the prepare phase (to determine the width),
+    /// so there is nothing left to collect
+    async fn collect(self: Box<Self>, _global_config: &GlobalConfig) -> BoxedCollected {
+        self
+    }
+}
+
+impl Collected for CgStats {
+    fn print(
+        self: Box<Self>,
+        global_config: &GlobalConfig,
+        width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
         let prepared = if let Some(prepared) = self.prepared {
             prepared
         } else {
-            return;
+            return Ok(());
         };
         let secs = prepared.time_span.as_secs();
         let rounded_time = if secs < 180 {
@@ -85,33 +137,80 @@ impl Component for CgStats {
         } else {
             Duration::from_secs((secs + 30) / 60 * 60)
         };
-        println!(
+        writeln!(
+            out,
             "CPU usage in the past {}:{}",
             humantime::format_duration(rounded_time),
             if prepared.users.len() + prepared.services.len() == 0 {
-                format!(" {}almost idle{}", color::Fg(color::Green), style::Reset)
+                format!(
+                    " {}almost idle{}",
+                    global_config.theme.ok.fg(),
+                    style::Reset
+                )
             } else {
                 "".into()
             }
-        );
+        )?;
         let indent = " ".repeat(INDENT_WIDTH);
-        let width = width.unwrap_or(global_config.progress_width - INDENT_WIDTH);
-        let bar_width = width - INDENT_WIDTH - prepared.max_name_width - 1 - 5;
+        let width = width.unwrap_or(
+            global_config
+                .progress_width
+                .fallback()
+                .saturating_sub(INDENT_WIDTH),
+        );
+        // On narrow terminals, the names are truncated to keep a minimal bar
+        let name_width = prepared
+            .max_name_width
+            .min(width.saturating_sub(INDENT_WIDTH + 1 + 5 + MIN_BAR_WIDTH));
+        let bar_width = width.saturating_sub(INDENT_WIDTH + name_width + 1 + 5);
         for (title, data) in [("Users", &prepared.users), ("Services", &prepared.services)] {
             if !data.is_empty() {
-                println!("{indent}{title}:");
+                writeln!(out, "{indent}{title}:")?;
             }
             for stat in data {
-                println!(
+                writeln!(
+                    out,
                     "{indent}{indent}{name:<width$} {percent:3.0}% {bar}",
-                    name = stat.name,
-                    bar = format_bar(global_config, bar_width, stat.load),
+                    name = truncate_str(&stat.name, name_width),
+                    bar = ProgressBar {
+                        ratio: stat.load,
+                        warn: self.warn,
+                        critical: self.critical,
+                    }
+                    .render(global_config, bar_width),
                     percent = stat.load * 100.0,
-                    width = prepared.max_name_width,
-                );
+                    width = name_width,
+                )?;
             }
         }
-        println!();
+        writeln!(out)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        match &self.prepared {
+            Some(prepared) => json!({
+                "time_span_secs": prepared.time_span.as_secs(),
+                "users": prepared.users,
+                "services": prepared.services,
+            }),
+            None => json!(null),
+        }
+    }
+
+    fn issues(&self) -> Vec<Issue> {
+        let Some(prepared) = &self.prepared else {
+            return vec![];
+        };
+        prepared
+            .users
+            .iter()
+            .chain(&prepared.services)
+            .filter_map(|stat| {
+                let severity = Level::from_usage(stat.load, self.warn, self.critical).into();
+                let message = format!("{} {:.0}% CPU", stat.name, stat.load * 100.0);
+                (severity != Severity::Ok).then(|| Issue::new(severity, message))
+            })
+            .collect()
     }
 }
 
@@ -142,22 +241,19 @@ pub enum CgStatsError {
 impl CgStats {
     pub fn prepare_or_error(
         &self,
-        _global_config: &GlobalConfig,
+        global_config: &GlobalConfig,
     ) -> Result<PreparedCgStats, CgStatsError> {
-        let num_cpus = available_parallelism()?.get();
-        let now = read_cg_state()?;
+        let num_cpus = global_config.sysroot.cpus()?;
+        let now = read_cg_state(&global_config.sysroot)?;
 
         let mut prepared_cg_stats = PreparedCgStats::default();
 
-        if let Ok(before) = fs::read_to_string(&self.state_file)
-            .inspect_err(|e| eprintln!("Reading {} failed: {e}", self.state_file))
-            .and_then(|s| {
-                toml::from_str::<State>(&s).map_err(|e| {
-                    eprintln!("Parsing TOML from {} failed: {e}", self.state_file);
-                    io::Error::other(e)
-                })
-            })
-        {
+        // A missing or invalid previous state (like on the first run) is not an error,
+        // there is simply nothing to print until the next run
+        let before = fs::read_to_string(&self.state_file)
+            .ok()
+            .and_then(|s| toml::from_str::<State>(&s).ok());
+        if let Some(before) = before {
             // Calculate the statistics
             let time_span = now
                 .time
@@ -199,40 +295,6 @@ struct State {
     system: HashMap<String, CgStat>, // system.slice
 }
 
-fn full_color(ratio: f64) -> String {
-    match (ratio * 100.) as usize {
-        0..=75 => color::Fg(color::Green).to_string(),
-        76..=95 => color::Fg(color::Yellow).to_string(),
-        _ => color::Fg(color::Red).to_string(),
-    }
-}
-
-fn format_bar(global_config: &GlobalConfig, width: usize, full_ratio: f64) -> String {
-    let without_ends_width =
-        width - global_config.progress_suffix.len() - global_config.progress_prefix.len();
-
-    let bar_full = ((without_ends_width as f64) * full_ratio.clamp(0.0, 1.0)).round() as usize;
-    let bar_empty = without_ends_width - bar_full;
-    let full_color = full_color(full_ratio);
-
-    [
-        global_config.progress_prefix.to_string(),
-        full_color,
-        global_config
-            .progress_full_character
-            .to_string()
-            .repeat(bar_full),
-        color::Fg(color::LightBlack).to_string(),
-        global_config
-            .progress_empty_character
-            .to_string()
-            .repeat(bar_empty),
-        style::Reset.to_string(),
-        global_config.progress_suffix.to_string(),
-    ]
-    .join("")
-}
-
 /// Calculate CPU usage from two states taken at different times. The
 /// result will include only Cgroups with CPU usage >= threshold.
 fn get_prepared_stats(
@@ -289,11 +351,15 @@ fn read_cg_stat(cg_path: &Path) -> Result<CgStat, CgStatsError> {
 /// Read statistics from direct children of a Cgroup given by `slice`.
 /// The keys of the returned hash map are the names of Cgroups passed
 /// through the `rename_key` function.
-fn read_stats<F>(slice: &str, rename_key: F) -> Result<HashMap<String, CgStat>, CgStatsError>
+fn read_stats<F>(
+    sysroot: &SysRoot,
+    slice: &str,
+    rename_key: F,
+) -> Result<HashMap<String, CgStat>, CgStatsError>
 where
     F: Fn(&str) -> String,
 {
-    let dir = ["/sys/fs/cgroup", slice].iter().collect::<PathBuf>();
+    let dir = sysroot.path("/sys/fs/cgroup").join(slice);
 
     let mut stats = HashMap::new();
     for entry in fs::read_dir(dir)? {
@@ -306,53 +372,18 @@ where
     Ok(stats)
 }
 
-// Copied and adapted from https://docs.rs/users/0.11.0/src/users/base.rs.html#326-360
-// Copyright (c) 2019 Benjamin Sago, MIT License
-fn get_username_by_uid(uid: uid_t) -> Option<CString> {
-    let mut passwd = unsafe { mem::zeroed::<c_passwd>() };
-    let mut buf = vec![0; 2048];
-    let mut result = ptr::null_mut::<c_passwd>();
-
-    loop {
-        let r =
-            unsafe { libc::getpwuid_r(uid, &mut passwd, buf.as_mut_ptr(), buf.len(), &mut result) };
-
-        if r != libc::ERANGE {
-            break;
-        }
-
-        let newsize = buf.len().checked_mul(2)?;
-        buf.resize(newsize, 0);
-    }
-
-    if result.is_null() {
-        // There is no such user, or an error has occurred.
-        // errno gets set if there’s an error.
-        return None;
-    }
-
-    if result != &mut passwd {
-        // The result of getpwuid_r should be its input passwd.
-        return None;
-    }
-    let ptr = unsafe { result.read() }.pw_name;
-    let name = unsafe { CStr::from_ptr(ptr) };
-    Some(CString::from(name))
-}
-
 lazy_static! {
     static ref SUFFIX_REGEX: Regex = Regex::new(r"\.service|\.scope|\.slice").unwrap();
     static ref UID_REGEX: Regex = Regex::new(r"^user-([0-9]+)\.slice$").unwrap();
 }
 
-fn key2username(key: &str) -> Result<String, String> {
+fn key2username(sysroot: &SysRoot, key: &str) -> Result<String, String> {
     let cap = UID_REGEX.captures(key).ok_or(key.to_owned())?;
     let uid = cap[1].parse::<u32>().map_err(|_| cap[1].to_owned())?;
-    let username = get_username_by_uid(uid).ok_or(cap[1].to_owned())?;
-    Ok(username.to_string_lossy().to_string())
+    sysroot.user_name(uid).ok_or(cap[1].to_owned())
 }
 
-fn read_cg_state() -> Result<State, CgStatsError> {
+fn read_cg_state(sysroot: &SysRoot) -> Result<State, CgStatsError> {
     let mut state = State {
         time: SystemTime::now(),
         user: HashMap::new(),
@@ -360,7 +391,7 @@ fn read_cg_state() -> Result<State, CgStatsError> {
     };
     // Read statistics of system services and shorten too long names, e.g.,
     // docker-dcd9a8c71b756de71a4a837c005840f84e0ed92574704ae1c89409c57980aaee.scope
-    state.system = read_stats("system.slice", |key| {
+    state.system = read_stats(sysroot, "system.slice", |key| {
         let name_no_suffix = SUFFIX_REGEX.replace(key, "");
         let max_len = 23;
         if name_no_suffix.len() <= max_len {
@@ -374,11 +405,13 @@ fn read_cg_state() -> Result<State, CgStatsError> {
     })?;
 
     // Read statistics of users and convert UIDs to user names
-    state.user = read_stats("user.slice", |key| match key2username(key) {
-        Ok(usename) => usename,
-        Err(fallback) => {
-            eprint!("warning: Cannot determine user name for {key}");
-            fallback
+    state.user = read_stats(sysroot, "user.slice", |key| {
+        match key2username(sysroot, key) {
+            Ok(usename) => usename,
+            Err(fallback) => {
+                eprint!("warning: Cannot determine user name for {key}");
+                fallback
+            }
         }
     })?;
     Ok(state)
@@ -390,16 +423,21 @@ mod tests {
 
     #[test]
     fn test_key2username() {
-        // This can fail if UID 0 is not named root
-        assert_eq!(key2username("user-0.slice"), Ok(String::from("root")));
-        assert_eq!(key2username("xxx"), Err(String::from("xxx")));
+        let sysroot = SysRoot::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/sysroot"
+        ));
+        assert_eq!(
+            key2username(&sysroot, "user-1000.slice"),
+            Ok(String::from("alice"))
+        );
+        assert_eq!(key2username(&sysroot, "xxx"), Err(String::from("xxx")));
         assert_eq!(
-            key2username("user-x.slice"),
+            key2username(&sysroot, "user-x.slice"),
             Err(String::from("user-x.slice"))
         );
-        // This can fail if your system uses UID 4294967286
         assert_eq!(
-            key2username("user-4294967286.slice"),
+            key2username(&sysroot, "user-4294967286.slice"),
             Err(String::from("4294967286"))
         );
     }
@@ -1,16 +1,19 @@
 use async_trait::async_trait;
-use serde::Deserialize;
-use termion::{color, style};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::io::{self, Write};
+use termion::style;
 use thiserror::Error;
 
 use crate::command::{BetterCommand, BetterCommandError};
-use crate::component::Component;
+use crate::component::{BoxedCollected, Collected, CollectedError, Component, ComponentError};
 use crate::config::global_config::GlobalConfig;
+use crate::config::theme::{Color, NamedColor};
 use crate::default_prepare;
 
 #[derive(knus::Decode, Debug, Deserialize)]
 pub struct Command {
-    #[knus(property, default=Color::White)]
+    #[knus(property, default=Color::Named(NamedColor::White))]
     color: Color,
     #[knus(argument)]
     command: String,
@@ -18,35 +21,18 @@ pub struct Command {
 
 #[async_trait]
 impl Component for Command {
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
-        self.print_or_error()
-            .unwrap_or_else(|err| println!("Command error: {err}"));
-        println!();
+    async fn collect(self: Box<Self>, _global_config: &GlobalConfig) -> BoxedCollected {
+        match self.collect_or_error().await {
+            Ok(collected) => Box::new(collected),
+            Err(err) => Box::new(CollectedError(ComponentError::new("command", err))),
+        }
+    }
+    fn name(&self) -> &'static str {
+        "command"
     }
     default_prepare!();
 }
 
-#[derive(knus::DecodeScalar, Debug, Deserialize)]
-#[serde(rename_all = "snake_case")]
-enum Color {
-    Black,
-    Red,
-    Green,
-    Yellow,
-    Blue,
-    Magenta,
-    Cyan,
-    White,
-    LightBlack,
-    LightRed,
-    LightGreen,
-    LightYellow,
-    LightBlue,
-    LightMagenta,
-    LightCyan,
-    LightWhite,
-}
-
 #[derive(Error, Debug)]
 pub enum CommandError {
     #[error(transparent)]
@@ -56,35 +42,68 @@ pub enum CommandError {
     IOError(#[from] std::io::Error),
 }
 
+#[derive(Serialize)]
+struct CollectedCommand {
+    #[serde(skip)]
+    color: Color,
+    output: String,
+}
+
+impl Collected for CollectedCommand {
+    fn print(
+        self: Box<Self>,
+        _global_config: &GlobalConfig,
+        _width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        writeln!(
+            out,
+            "{}{}{}",
+            self.color.fg(),
+            &self.output.trim_end(),
+            style::Reset
+        )?;
+        writeln!(out)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!(self)
+    }
+}
+
 impl Command {
-    pub fn print_or_error(self) -> Result<(), CommandError> {
+    async fn collect_or_error(self) -> Result<CollectedCommand, CommandError> {
         // We probably don't have to handle command not found for sh
-        let output = BetterCommand::new("sh")
-            .arg("-c")
-            .arg(&self.command)
-            .check_status_and_get_output_string()?;
+        // The command is killed if it is still running when the component times out
+        let mut command = BetterCommand::new("sh");
+        command.arg("-c").arg(&self.command);
+        let output = command.check_status_and_get_output_string_async().await?;
 
-        let color = match self.color {
-            Color::Black => color::Black.fg_str(),
-            Color::Red => color::Red.fg_str(),
-            Color::Yellow => color::Yellow.fg_str(),
-            Color::Green => color::Green.fg_str(),
-            Color::Blue => color::Blue.fg_str(),
-            Color::Magenta => color::Magenta.fg_str(),
-            Color::Cyan => color::Cyan.fg_str(),
-            Color::White => color::White.fg_str(),
-            Color::LightBlack => color::LightBlack.fg_str(),
-            Color::LightRed => color::LightRed.fg_str(),
-            Color::LightYellow => color::LightYellow.fg_str(),
-            Color::LightGreen => color::LightGreen.fg_str(),
-            Color::LightBlue => color::LightBlue.fg_str(),
-            Color::LightMagenta => color::LightMagenta.fg_str(),
-            Color::LightCyan => color::LightCyan.fg_str(),
-            Color::LightWhite => color::LightWhite.fg_str(),
-        };
+        Ok(CollectedCommand {
+            color: self.color,
+            output,
+        })
+    }
+}
 
-        println!("{}{}{}", color, &output.trim_end(), style::Reset);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use termion::color;
 
-        Ok(())
+    #[test]
+    fn test_print_to_buffer() {
+        let collected = Box::new(CollectedCommand {
+            color: Color::Named(NamedColor::Red),
+            output: String::from("hello\n"),
+        });
+        let mut out = Vec::new();
+        collected
+            .print(&GlobalConfig::default(), None, &mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{}hello{}\n\n", color::Red.fg_str(), style::Reset)
+        );
     }
 }
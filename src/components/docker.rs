@@ -3,11 +3,17 @@ use async_trait::async_trait;
 use docker_api::models::ContainerSummary;
 use docker_api::opts::ContainerListOpts;
 use docker_api::{Docker as DockerAPI, Result as DockerResult};
+use serde::Serialize;
+use serde_json::json;
 use std::collections::HashMap;
+use std::io::{self, Write};
 use termion::{color, style};
 
-use crate::component::Component;
+use crate::component::{
+    BoxedCollected, Collected, CollectedError, Component, ComponentError, Issue, Severity,
+};
 use crate::config::global_config::GlobalConfig;
+use crate::config::theme::{Level, Theme};
 use crate::default_prepare;
 
 #[cfg(unix)]
@@ -40,58 +46,115 @@ pub struct Docker {
 
 #[async_trait]
 impl Component for Docker {
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
-        println!("{}:", self.title);
-        self.print_or_error()
-            .await
-            .unwrap_or_else(|err| println!("Docker status error: {err}"));
-        println!();
+    async fn collect(self: Box<Self>, _global_config: &GlobalConfig) -> BoxedCollected {
+        match self.collect_or_error().await {
+            Ok(collected) => Box::new(collected),
+            Err(err) => Box::new(CollectedError(
+                ComponentError::new("docker", err).with_title(format!("{}:", self.title)),
+            )),
+        }
+    }
+    fn name(&self) -> &'static str {
+        "docker"
+    }
+    async fn check(self: Box<Self>, _global_config: &GlobalConfig) -> Vec<String> {
+        check_socket(&self.socket).await
     }
     default_prepare!();
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Container {
-    pub summary: ContainerSummary,
     pub name: String,
+    pub state: Option<String>,
+    pub status: Option<String>,
+}
+
+impl Container {
+    pub fn new(name: String, summary: &ContainerSummary) -> Self {
+        Container {
+            name,
+            state: summary.state.clone(),
+            status: summary.status.clone(),
+        }
+    }
 }
 
 pub fn init_api(socket: &str) -> DockerResult<DockerAPI> {
     DockerAPI::new(socket)
 }
 
-pub fn state_to_color(state: &str) -> String {
+/// Check that the Docker daemon can be reached through the socket
+pub async fn check_socket(socket: &str) -> Vec<String> {
+    let result = match init_api(socket) {
+        Ok(api) => api.ping().await.map(|_| ()),
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(()) => vec![],
+        Err(err) => vec![format!("Cannot reach the Docker daemon at {socket}: {err}")],
+    }
+}
+
+/// The level of the state of a container, if it is one that has a colour
+fn state_level(state: &str) -> Option<Level> {
     match state {
-        "created" | "restarting" | "paused" | "removing" | "configured" => {
-            color::Fg(color::Yellow).to_string()
-        }
-        "running" => color::Fg(color::Green).to_string(),
-        "exited" => color::Fg(color::LightBlack).to_string(),
-        "dead" => color::Fg(color::Red).to_string(),
-        _ => color::Fg(color::White).to_string(),
+        "created" | "restarting" | "paused" | "removing" | "configured" => Some(Level::Warn),
+        "running" => Some(Level::Ok),
+        "dead" => Some(Level::Critical),
+        _ => None,
+    }
+}
+
+pub fn state_to_color(theme: &Theme, state: &str) -> String {
+    match (state_level(state), state) {
+        (Some(level), _) => theme.level(level),
+        (None, "exited") => theme.muted(),
+        (None, _) => color::Fg(color::White).to_string(),
     }
 }
 
-pub fn print_containers(containers: Vec<Container>, indent_width: usize, padding: usize) {
+/// The containers that are not running well (exited containers are not a problem)
+pub fn container_issues<'a>(containers: impl IntoIterator<Item = &'a Container>) -> Vec<Issue> {
+    containers
+        .into_iter()
+        .filter_map(|container| {
+            let state = container.state.as_deref()?.to_lowercase();
+            let severity = state_level(&state)?.into();
+            let message = format!("{} {state}", container.name);
+            (severity != Severity::Ok).then(|| Issue::new(severity, message))
+        })
+        .collect()
+}
+
+pub fn print_containers(
+    theme: &Theme,
+    containers: Vec<Container>,
+    indent_width: usize,
+    padding: usize,
+    out: &mut dyn Write,
+) -> io::Result<()> {
     for container in containers {
         let status_color = state_to_color(
+            theme,
             container
-                .summary
                 .state
                 .map(|s| s.to_lowercase())
                 .as_deref()
                 .unwrap_or(""),
         );
-        println!(
+        writeln!(
+            out,
             "{indent}{name}: {padding}{color}{status}{reset}",
             indent = " ".repeat(indent_width),
             name = container.name,
             padding = " ".repeat(padding - container.name.len()),
             color = status_color,
-            status = container.summary.status.unwrap_or(String::from("?")),
+            status = container.status.unwrap_or(String::from("?")),
             reset = style::Reset,
-        );
+        )?;
     }
+    Ok(())
 }
 
 impl Docker {
@@ -103,7 +166,9 @@ impl Docker {
         }
     }
 
-    pub async fn print_or_error(&self) -> Result<(), Box<dyn std::error::Error>> {
+    async fn collect_or_error(
+        &self,
+    ) -> Result<CollectedDocker, Box<dyn std::error::Error + Send + Sync>> {
         let api = init_api(&self.socket)?;
 
         // Get all containers from library
@@ -123,39 +188,91 @@ impl Docker {
                 })
             })
             .collect();
+
+        let mut missing = vec![];
         let containers: Vec<Container> = self
             .containers
             .iter()
             .filter_map(
-                |DockerContainer { docker_name, display_name }| match summary_hash.get(docker_name) {
-                    Some(&summary) => Some(Container {
-                        name: display_name.clone(),
-                        summary: summary.clone(),
-                    }),
+                |DockerContainer {
+                     docker_name,
+                     display_name,
+                 }| match summary_hash.get(docker_name) {
+                    Some(&summary) => Some(Container::new(display_name.clone(), summary)),
                     None => {
-                        println!(
-                            "{indent}{color}Warning: Could not find container `{docker_name}'{reset}",
-                            indent = " ".repeat(INDENT_WIDTH),
-                            color = color::Fg(color::Yellow),
-                            docker_name = docker_name,
-                            reset = style::Reset
-                        );
+                        missing.push(docker_name.clone());
                         None
                     }
                 },
             )
             .collect();
 
+        Ok(CollectedDocker {
+            title: self.title.clone(),
+            missing,
+            containers,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct CollectedDocker {
+    title: String,
+    /// Names of the configured containers that were not found
+    missing: Vec<String>,
+    containers: Vec<Container>,
+}
+
+impl Collected for CollectedDocker {
+    fn print(
+        self: Box<Self>,
+        global_config: &GlobalConfig,
+        _width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        writeln!(out, "{}:", self.title)?;
+
+        for docker_name in self.missing {
+            writeln!(
+                out,
+                "{indent}{color}Warning: Could not find container `{docker_name}'{reset}",
+                indent = " ".repeat(INDENT_WIDTH),
+                color = global_config.theme.level(Level::Warn),
+                docker_name = docker_name,
+                reset = style::Reset
+            )?;
+        }
+
         // Max length of all the container names (first column)
         // to determine the padding
-        let max_container_name = containers
+        let max_container_name = self
+            .containers
             .iter()
             .map(|container| container.name.len())
             .max()
             .unwrap_or(0);
 
-        print_containers(containers, INDENT_WIDTH, max_container_name);
+        print_containers(
+            &global_config.theme,
+            self.containers,
+            INDENT_WIDTH,
+            max_container_name,
+            out,
+        )?;
+        writeln!(out)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!(self)
+    }
 
-        Ok(())
+    fn issues(&self) -> Vec<Issue> {
+        self.missing
+            .iter()
+            .map(|docker_name| {
+                Issue::new(Severity::Warn, format!("container {docker_name} not found"))
+            })
+            .chain(container_issues(&self.containers))
+            .collect()
     }
 }
@@ -1,21 +1,29 @@
 use async_trait::async_trait;
 use docker_api::opts::{ContainerFilter, ContainerListOpts};
 use itertools::Itertools;
+use serde::Serialize;
+use serde_json::json;
 use shellexpand;
 use std::fs;
-use termion::{color, style};
+use std::io::{self, Write};
+use std::path::Path;
+use termion::style;
 
-use crate::component::Component;
+use crate::component::{
+    BoxedCollected, Collected, CollectedError, Component, ComponentError, Issue, Severity,
+};
 use crate::components::docker::{
-    init_api, print_containers, state_to_color, Container, DEFAULT_SOCKET,
+    check_socket, container_issues, init_api, print_containers, state_to_color, Container,
+    DEFAULT_SOCKET,
 };
 use crate::config::global_config::GlobalConfig;
+use crate::config::theme::{Level, Theme};
 use crate::constants::INDENT_WIDTH;
 use crate::default_prepare;
 
 const DEFAULT_TITLE: &str = "Docker Compose";
 
-#[derive(knus::DecodeScalar, Debug, Default)]
+#[derive(knus::DecodeScalar, Debug, Default, Clone, Copy)]
 pub enum DockerComposeStyle {
     #[default]
     Count,
@@ -45,10 +53,11 @@ pub struct DockerCompose {
     pub style: DockerComposeStyle,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 struct PreparedStack {
     display_name: String,
 
+    #[serde(skip)]
     max_container_name: usize,
 
     containers: Vec<Container>,
@@ -77,12 +86,27 @@ static SIMILAR_STATES: &[SimilarStates] = &[
 
 #[async_trait]
 impl Component for DockerCompose {
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
-        println!("{}:", self.title);
-        self.print_or_error()
-            .await
-            .unwrap_or_else(|err| println!("{} status error: {}", self.title, err));
-        println!();
+    async fn collect(self: Box<Self>, _global_config: &GlobalConfig) -> BoxedCollected {
+        match self.collect_or_error().await {
+            Ok(collected) => Box::new(collected),
+            Err(err) => Box::new(CollectedError(
+                ComponentError::new("docker-compose", err).with_title(format!("{}:", self.title)),
+            )),
+        }
+    }
+    fn name(&self) -> &'static str {
+        "docker-compose"
+    }
+    async fn check(self: Box<Self>, _global_config: &GlobalConfig) -> Vec<String> {
+        let mut problems = check_socket(&self.socket).await;
+        for ComposeStack { path, display_name } in &self.stacks {
+            if !Path::new(&*shellexpand::tilde(path)).exists() {
+                problems.push(format!(
+                    "Path {path} of stack {display_name} does not exist"
+                ));
+            }
+        }
+        problems
     }
     default_prepare!();
 }
@@ -97,10 +121,13 @@ impl DockerCompose {
         }
     }
 
-    pub async fn print_or_error(&self) -> Result<(), Box<dyn std::error::Error>> {
+    async fn collect_or_error(
+        &self,
+    ) -> Result<CollectedDockerCompose, Box<dyn std::error::Error + Send + Sync>> {
         let api = init_api(&self.socket)?;
 
         let mut prepared_stacks: Vec<PreparedStack> = vec![];
+        let mut not_found: Vec<String> = vec![];
 
         for ComposeStack { path, display_name } in self.stacks.iter() {
             let path = fs::canonicalize(&*shellexpand::tilde(path))?
@@ -121,13 +148,7 @@ impl DockerCompose {
                 .await?;
 
             if containers.is_empty() {
-                println!(
-                    "{indent}{display_name}: {color}Not found{reset}",
-                    indent = " ".repeat(INDENT_WIDTH * 2),
-                    display_name = display_name.clone(),
-                    color = color::Fg(color::Yellow),
-                    reset = style::Reset,
-                );
+                not_found.push(display_name.clone());
                 continue;
             }
 
@@ -149,10 +170,7 @@ impl DockerCompose {
                                 .map(|names| names[0].to_string())
                                 .unwrap_or_else(|| "unknown".to_string())
                         });
-                    Container {
-                        name,
-                        summary: container,
-                    }
+                    Container::new(name, &container)
                 })
                 .collect();
 
@@ -169,36 +187,116 @@ impl DockerCompose {
             });
         }
 
-        let max_container_name = prepared_stacks
+        Ok(CollectedDockerCompose {
+            title: self.title.clone(),
+            style: self.style,
+            not_found,
+            prepared_stacks,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct CollectedDockerCompose {
+    title: String,
+    #[serde(skip)]
+    style: DockerComposeStyle,
+    /// Display names of the stacks without any containers
+    not_found: Vec<String>,
+    #[serde(rename = "stacks")]
+    prepared_stacks: Vec<PreparedStack>,
+}
+
+impl Collected for CollectedDockerCompose {
+    fn print(
+        self: Box<Self>,
+        global_config: &GlobalConfig,
+        _width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        writeln!(out, "{}:", self.title)?;
+
+        for display_name in self.not_found.iter() {
+            writeln!(
+                out,
+                "{indent}{display_name}: {color}Not found{reset}",
+                indent = " ".repeat(INDENT_WIDTH * 2),
+                display_name = display_name,
+                color = global_config.theme.level(Level::Warn),
+                reset = style::Reset,
+            )?;
+        }
+
+        let max_container_name = self
+            .prepared_stacks
             .iter()
             .map(|stack| stack.max_container_name)
             .max()
             .unwrap_or(0);
 
         match self.style {
-            DockerComposeStyle::Full => self.print_full(prepared_stacks, max_container_name),
-            DockerComposeStyle::Count => self.print_count(prepared_stacks),
+            DockerComposeStyle::Full => Self::print_full(
+                &global_config.theme,
+                self.prepared_stacks,
+                max_container_name,
+                out,
+            )?,
+            DockerComposeStyle::Count => {
+                Self::print_count(&global_config.theme, self.prepared_stacks, out)?
+            }
         };
+        writeln!(out)
+    }
 
-        Ok(())
+    fn to_json(&self) -> serde_json::Value {
+        json!(self)
     }
 
-    fn print_full(&self, prepared_stacks: Vec<PreparedStack>, max_container_name: usize) {
+    fn issues(&self) -> Vec<Issue> {
+        self.not_found
+            .iter()
+            .map(|display_name| {
+                Issue::new(Severity::Warn, format!("stack {display_name} not found"))
+            })
+            .chain(container_issues(
+                self.prepared_stacks
+                    .iter()
+                    .flat_map(|stack| &stack.containers),
+            ))
+            .collect()
+    }
+}
+
+impl CollectedDockerCompose {
+    fn print_full(
+        theme: &Theme,
+        prepared_stacks: Vec<PreparedStack>,
+        max_container_name: usize,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
         for prepared_stack in prepared_stacks.into_iter() {
-            println!(
+            writeln!(
+                out,
                 "{indent}{}:",
                 prepared_stack.display_name,
                 indent = " ".repeat(INDENT_WIDTH)
-            );
+            )?;
             print_containers(
+                theme,
                 prepared_stack.containers,
                 2 * INDENT_WIDTH,
                 max_container_name,
-            );
+                out,
+            )?;
         }
+        Ok(())
     }
 
-    fn print_count(&self, prepared_stacks: Vec<PreparedStack>) {
+    fn print_count(
+        theme: &Theme,
+        prepared_stacks: Vec<PreparedStack>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
         let longest_display_name = prepared_stacks
             .iter()
             .map(|stack| stack.display_name.len())
@@ -211,11 +309,7 @@ impl DockerCompose {
                 .iter()
                 .map(|container| {
                     (
-                        container
-                            .summary
-                            .state
-                            .clone()
-                            .unwrap_or("unknown".to_owned()),
+                        container.state.clone().unwrap_or("unknown".to_owned()),
                         container,
                     )
                 })
@@ -237,7 +331,7 @@ impl DockerCompose {
                     }
                     Some(format!(
                         "{color}{count} {title}{reset}",
-                        color = state_to_color(similar_states.states[0]),
+                        color = state_to_color(theme, similar_states.states[0]),
                         title = similar_states.title,
                         count = count,
                         reset = style::Reset,
@@ -245,13 +339,15 @@ impl DockerCompose {
                 })
                 .join(" ");
 
-            println!(
+            writeln!(
+                out,
                 "{indent}{name}:{padding} {states}",
                 indent = " ".repeat(INDENT_WIDTH),
                 name = prepared_stack.display_name,
                 padding = " ".repeat(longest_display_name - prepared_stack.display_name.len()),
                 states = states,
-            );
+            )?;
         }
+        Ok(())
     }
 }
@@ -2,11 +2,13 @@ use crate::constants::INDENT_WIDTH;
 use async_trait::async_trait;
 use lazy_static::lazy_static;
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::io::{self, Write};
 use thiserror::Error;
 
 use crate::command::{BetterCommand, BetterCommandError};
-use crate::component::Component;
+use crate::component::{BoxedCollected, Collected, CollectedError, Component, ComponentError};
 use crate::config::global_config::GlobalConfig;
 use crate::default_prepare;
 
@@ -18,19 +20,30 @@ pub struct Fail2Ban {
 
 #[async_trait]
 impl Component for Fail2Ban {
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
-        self.print_or_error()
-            .unwrap_or_else(|err| println!("Fail2Ban error: {err}"));
-        println!();
+    async fn collect(self: Box<Self>, _global_config: &GlobalConfig) -> BoxedCollected {
+        match self.collect_or_error() {
+            Ok(collected) => Box::new(collected),
+            Err(err) => Box::new(CollectedError(ComponentError::new("fail2ban", err))),
+        }
+    }
+    fn name(&self) -> &'static str {
+        "fail2ban"
     }
     default_prepare!();
 }
 
+#[derive(Serialize)]
 struct Entry {
+    jail: String,
     total: u32,
     current: u32,
 }
 
+#[derive(Serialize)]
+struct CollectedFail2Ban {
+    entries: Vec<Entry>,
+}
+
 #[derive(Error, Debug)]
 pub enum Fail2BanError {
     #[error(transparent)]
@@ -43,7 +56,7 @@ pub enum Fail2BanError {
     IO(#[from] std::io::Error),
 }
 
-fn get_jail_status(jail: &str) -> Result<Entry, Fail2BanError> {
+fn get_jail_status(jail: String) -> Result<Entry, Fail2BanError> {
     lazy_static! {
         static ref TOTAL_REGEX: Regex = Regex::new(r"Total banned:\s+([0-9]+)").unwrap();
         static ref CURRENT_REGEX: Regex = Regex::new(r"Currently banned:\s+([0-9]+)").unwrap();
@@ -52,34 +65,58 @@ fn get_jail_status(jail: &str) -> Result<Entry, Fail2BanError> {
     let executable = "fail2ban-client";
     let output = BetterCommand::new(executable)
         .arg("status")
-        .arg(jail)
+        .arg(&jail)
         .check_status_and_get_output_string()?;
 
     let total = TOTAL_REGEX.captures_iter(&output).next().unwrap()[1].parse::<u32>()?;
     let current = CURRENT_REGEX.captures_iter(&output).next().unwrap()[1].parse::<u32>()?;
 
-    Ok(Entry { total, current })
+    Ok(Entry {
+        jail,
+        total,
+        current,
+    })
 }
 
-impl Fail2Ban {
-    pub fn print_or_error(self) -> Result<(), Fail2BanError> {
-        println!("Fail2Ban:");
+impl Collected for CollectedFail2Ban {
+    fn print(
+        self: Box<Self>,
+        _global_config: &GlobalConfig,
+        _width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        writeln!(out, "Fail2Ban:")?;
 
-        for jail in self.jails {
-            let entry = get_jail_status(&jail)?;
-            println!(
+        for entry in self.entries {
+            writeln!(
+                out,
                 concat!(
                     "{indent}{jail}:\n",
                     "{indent}{indent}Total bans:   {total}\n",
                     "{indent}{indent}Current bans: {current}",
                 ),
-                jail = jail,
+                jail = entry.jail,
                 total = entry.total,
                 current = entry.current,
                 indent = " ".repeat(INDENT_WIDTH),
-            );
+            )?;
         }
+        writeln!(out)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!(self)
+    }
+}
+
+impl Fail2Ban {
+    fn collect_or_error(self) -> Result<CollectedFail2Ban, Fail2BanError> {
+        let entries = self
+            .jails
+            .into_iter()
+            .map(get_jail_status)
+            .collect::<Result<Vec<Entry>, Fail2BanError>>()?;
 
-        Ok(())
+        Ok(CollectedFail2Ban { entries })
     }
 }
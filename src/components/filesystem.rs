@@ -2,17 +2,24 @@ use async_trait::async_trait;
 use bytesize::ByteSize;
 use indexmap::IndexMap;
 use itertools::Itertools;
+use serde::Serialize;
+use serde_json::json;
 use std::cmp;
+use std::io::{self, Write};
 use std::iter;
-use systemstat::{Filesystem, Platform, System};
-use termion::{color, style};
+use systemstat::Filesystem;
 use thiserror::Error;
 use unicode_ellipsis::truncate_str;
 
-use crate::component::{Component, Constraints, PrepareReturn};
+use crate::component::{
+    BoxedCollected, Collected, Component, ComponentError, Constraints, FailedComponent, Issue,
+    PerfData, PrepareReturn, Severity,
+};
 use crate::config::global_config::GlobalConfig;
+use crate::config::theme::{Level, DEFAULT_CRITICAL, DEFAULT_WARN};
 use crate::constants::INDENT_WIDTH;
 use crate::default_prepare;
+use crate::progress_bar::ProgressBar;
 
 const HEADER: [&str; 6] = ["Filesystems", "Device", "Mount", "Type", "Used", "Total"];
 
@@ -29,6 +36,14 @@ pub struct Mount {
 pub struct Filesystems {
     #[knus(children(name = "filesystem"))]
     pub mounts: Vec<Mount>,
+
+    /// Percentage of usage above which the bars are printed in the warn colour
+    #[knus(property, default = DEFAULT_WARN)]
+    pub warn: usize,
+
+    /// Percentage of usage above which the bars are printed in the critical colour
+    #[knus(property, default = DEFAULT_CRITICAL)]
+    pub critical: usize,
 }
 
 #[async_trait]
@@ -36,15 +51,41 @@ impl Component for Filesystems {
     fn prepare(self: Box<Self>, global_config: &GlobalConfig) -> PrepareReturn {
         match self.clone().prepare_or_error(global_config) {
             Ok(prepared_component) => prepared_component,
-            Err(err) => {
-                eprintln!("Filesystems error: {err}");
-                None
-            }
+            Err(err) => Some((
+                Box::new(FailedComponent(ComponentError::new("filesystems", err))),
+                None,
+            )),
         }
     }
 
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
-        unreachable!("Print should never be called on a raw `Filesystems`. Prepare should be called, returning a `PreparedFilesystems`.");
+    fn name(&self) -> &'static str {
+        "filesystems"
+    }
+
+    async fn check(self: Box<Self>, global_config: &GlobalConfig) -> Vec<String> {
+        let mounts = match global_config.sysroot.mounts() {
+            Ok(mounts) => mounts,
+            Err(err) => return vec![format!("Could not list the mount points: {err}")],
+        };
+        self.mounts
+            .iter()
+            .filter(|mount| {
+                !mounts
+                    .iter()
+                    .any(|fs| fs.fs_mounted_on == mount.mount_point)
+            })
+            .map(|mount| {
+                format!(
+                    "Mount point {} of {} is not mounted",
+                    mount.mount_point, mount.name
+                )
+            })
+            .chain(Level::check_thresholds(self.warn, self.critical))
+            .collect()
+    }
+
+    async fn collect(self: Box<Self>, _global_config: &GlobalConfig) -> BoxedCollected {
+        unreachable!("Collect should never be called on a raw `Filesystems`. Prepare should be called, returning a `PreparedFilesystems`.");
     }
 }
 
@@ -52,24 +93,74 @@ impl Component for Filesystems {
 /// This is returned from the prepare phase
 /// It is generated based on the user's configuration stored in `Filesystems`
 /// and has all the information needed for printing
+#[derive(Debug)]
 struct PreparedFilesystems {
     column_sizes: Vec<usize>,
     entries: Vec<Entry>,
-    bar_width: usize,
+    warn: usize,
+    critical: usize,
 }
 
+/// The filesystems are already read in the prepare phase (to determine the width),
+/// so there is nothing left to collect
 #[async_trait]
 impl Component for PreparedFilesystems {
-    async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
-        self.print_or_error(global_config).unwrap_or_else(|err| {
-            println!("Filesystem error: {err}");
-        });
-        println!();
+    async fn collect(self: Box<Self>, _global_config: &GlobalConfig) -> BoxedCollected {
+        self
     }
 
+    fn name(&self) -> &'static str {
+        "filesystems"
+    }
     default_prepare!();
 }
 
+impl Collected for PreparedFilesystems {
+    fn print(
+        self: Box<Self>,
+        global_config: &GlobalConfig,
+        width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        self.print_or_error(global_config, width, out)?;
+        writeln!(out)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({ "filesystems": self.entries })
+    }
+
+    fn issues(&self) -> Vec<Issue> {
+        self.entries
+            .iter()
+            .filter_map(|entry| {
+                let severity = Level::from_usage(entry.used_ratio, self.warn, self.critical).into();
+                let message = format!(
+                    "{} {:.0}% full",
+                    entry.mount_point,
+                    entry.used_ratio * 100.0
+                );
+                (severity != Severity::Ok).then(|| Issue::new(severity, message))
+            })
+            .collect()
+    }
+
+    fn perfdata(&self) -> Vec<PerfData> {
+        self.entries
+            .iter()
+            .map(|entry| {
+                PerfData::usage(
+                    &entry.mount_point,
+                    entry.used_bytes,
+                    entry.total_bytes,
+                    self.warn,
+                    self.critical,
+                )
+            })
+            .collect()
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum FilesystemsError {
     #[error("Could not find mount {mount_point:?}")]
@@ -80,14 +171,18 @@ pub enum FilesystemsError {
 }
 
 /// Data needed to print one row of the filesystems table
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 struct Entry {
     filesystem_name: String,
     dev: String,
     mount_point: String,
     fs_type: String,
+    #[serde(skip)]
     used: String,
+    #[serde(skip)]
     total: String,
+    used_bytes: u64,
+    total_bytes: u64,
     used_ratio: f64,
 }
 
@@ -103,40 +198,49 @@ fn parse_into_entry(filesystem_name: String, mount: &Filesystem) -> Entry {
         fs_type: mount.fs_type.to_string(),
         used: ByteSize::b(used).to_string(),
         total: ByteSize::b(total).to_string(),
+        used_bytes: used,
+        total_bytes: total,
         used_ratio: (used as f64) / (total as f64),
     }
 }
 
-fn print_row<'a>(items: [&str; 6], column_sizes: impl IntoIterator<Item = &'a usize>) {
-    println!(
+fn print_row<'a>(
+    items: [&str; 6],
+    column_sizes: impl IntoIterator<Item = &'a usize>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    writeln!(
+        out,
         "{}",
         Itertools::intersperse(
             items
                 .iter()
                 .zip(column_sizes.into_iter())
-                .map(|(name, size)| format!("{name: <size$}")),
+                .map(|(name, size)| format!("{: <size$}", truncate_str(name, *size))),
             " ".repeat(INDENT_WIDTH)
         )
         .collect::<String>()
-    );
+    )
 }
 
 impl Filesystems {
     pub fn new(mounts: Vec<Mount>) -> Self {
-        Self { mounts }
+        Self {
+            mounts,
+            warn: DEFAULT_WARN,
+            critical: DEFAULT_CRITICAL,
+        }
     }
 
     fn prepare_or_error(
         self,
         global_config: &GlobalConfig,
     ) -> Result<PrepareReturn, FilesystemsError> {
-        let sys = System::new();
-
         if self.mounts.is_empty() {
             return Ok(None);
         }
 
-        let mounts = sys.mounts()?;
+        let mounts = global_config.sysroot.mounts()?;
         let mounts: IndexMap<String, &Filesystem> = mounts
             .iter()
             .map(|fs| (fs.fs_mounted_on.clone(), fs))
@@ -172,22 +276,15 @@ impl Filesystems {
                     .collect()
             });
 
-        // -2 because "Filesystems" does not count (it is not indented)
-        // and because zero indexed
-        let bar_width = column_sizes.iter().sum::<usize>() + (HEADER.len() - 2) * INDENT_WIDTH
-            - global_config.progress_prefix.len()
-            - global_config.progress_suffix.len();
-        let fs_display_width =
-            bar_width + global_config.progress_prefix.len() + global_config.progress_suffix.len();
-
         let prepared_filesystems = PreparedFilesystems {
-            bar_width,
             column_sizes,
             entries,
+            warn: self.warn,
+            critical: self.critical,
         };
 
         let constraints = Constraints {
-            min_width: Some(fs_display_width),
+            min_width: Some(prepared_filesystems.display_width()),
         };
 
         Ok(Some((Box::new(prepared_filesystems), Some(constraints))))
@@ -195,13 +292,43 @@ impl Filesystems {
 }
 
 impl PreparedFilesystems {
-    fn print_or_error(self, global_config: &GlobalConfig) -> Result<(), FilesystemsError> {
-        print_row(HEADER, &self.column_sizes);
+    /// Width of the table (without the first indentation) when no column is truncated
+    fn display_width(&self) -> usize {
+        // -2 because "Filesystems" does not count (it is not indented)
+        // and because zero indexed
+        self.column_sizes.iter().sum::<usize>() + (HEADER.len() - 2) * INDENT_WIDTH
+    }
 
-        for entry in self.entries {
-            let bar_full = ((self.bar_width as f64) * entry.used_ratio) as usize;
-            let bar_empty = self.bar_width - bar_full;
+    /// Shrink the text columns (name, device, mount point and type) to fit in `width`,
+    /// always taking from the widest one
+    /// The columns never get narrower than their header, so the table may still overflow
+    fn shrink_columns(&mut self, width: usize) {
+        let mut excess = self.display_width().saturating_sub(width);
+        while excess > 0 {
+            let widest = (0..4)
+                .filter(|&i| self.column_sizes[i] > HEADER[i].len())
+                .max_by_key(|&i| self.column_sizes[i]);
+            match widest {
+                Some(i) => self.column_sizes[i] -= 1,
+                None => break,
+            }
+            excess -= 1;
+        }
+    }
+
+    fn print_or_error(
+        mut self,
+        global_config: &GlobalConfig,
+        width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        // The bars span the whole width, while the table is shrunk if it is wider
+        let width = width.unwrap_or(self.display_width());
+        self.shrink_columns(width);
+
+        print_row(HEADER, &self.column_sizes, out)?;
 
+        for entry in self.entries {
             print_row(
                 [
                     &[" ".repeat(INDENT_WIDTH), entry.filesystem_name].concat(),
@@ -212,36 +339,91 @@ impl PreparedFilesystems {
                     entry.total.as_str(),
                 ],
                 &self.column_sizes,
-            );
+                out,
+            )?;
 
-            let full_color = match (entry.used_ratio * 100.0) as usize {
-                0..=75 => color::Fg(color::Green).to_string(),
-                76..=95 => color::Fg(color::Yellow).to_string(),
-                _ => color::Fg(color::Red).to_string(),
+            let bar = ProgressBar {
+                ratio: entry.used_ratio,
+                warn: self.warn,
+                critical: self.critical,
             };
-
-            println!(
-                "{}",
-                [
-                    " ".repeat(INDENT_WIDTH),
-                    global_config.progress_prefix.to_string(),
-                    full_color,
-                    global_config
-                        .progress_full_character
-                        .to_string()
-                        .repeat(bar_full),
-                    color::Fg(color::LightBlack).to_string(),
-                    global_config
-                        .progress_empty_character
-                        .to_string()
-                        .repeat(bar_empty),
-                    style::Reset.to_string(),
-                    global_config.progress_suffix.to_string(),
-                ]
-                .join("")
-            );
+            writeln!(
+                out,
+                "{}{}",
+                " ".repeat(INDENT_WIDTH),
+                bar.render(global_config, width)
+            )?;
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sysroot::{MountUsage, SysRoot};
+    use std::path::Path;
+
+    /// The usage of the filesystems of the fixtures, which are not actually mounted
+    fn fixture_statvfs(path: &Path) -> io::Result<MountUsage> {
+        let (blocks, blocks_avail) = match path.file_name().and_then(|name| name.to_str()) {
+            Some("sysroot") => (5242880, 2621440),
+            Some("data") => (262144000, 23592960),
+            _ => return Err(io::ErrorKind::NotFound.into()),
+        };
+        Ok(MountUsage {
+            block_size: 4096,
+            blocks,
+            blocks_free: blocks_avail,
+            blocks_avail,
+            files: 1000000,
+            files_free: 500000,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_filesystems() {
+        let global_config = GlobalConfig {
+            sysroot: SysRoot::new(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/sysroot"
+            ))
+            .with_statvfs(fixture_statvfs),
+            progress_empty_character: "-".to_string(),
+            ..GlobalConfig::default()
+        };
+        let filesystems = Box::new(Filesystems::new(vec![
+            Mount {
+                name: "root".to_string(),
+                mount_point: "/".to_string(),
+            },
+            Mount {
+                name: "data".to_string(),
+                mount_point: "/data".to_string(),
+            },
+        ]));
+        let (prepared, constraints) = filesystems.prepare(&global_config).unwrap();
+        let width = constraints.unwrap().min_width;
+        let mut out = Vec::new();
+        prepared
+            .collect(&global_config)
+            .await
+            .print(&global_config, width, &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(
+            [lines[0], lines[1], lines[3]],
+            [
+                "Filesystems  Device     Mount  Type  Used       Total     ",
+                "  root       /dev/sda1  /      ext4  10.0 GiB   20.0 GiB  ",
+                "  data       /dev/sdb1  /data  xfs   910.0 GiB  1000.0 GiB",
+            ]
+        );
+        // The bars have the width of the table, and are half and 90% full
+        let bar = |line: &str| (line.matches('=').count(), line.matches('-').count());
+        assert_eq!(bar(lines[2]), (27, 27));
+        assert_eq!(bar(lines[4]), (49, 5));
+    }
+}
@@ -2,16 +2,19 @@ use async_trait::async_trait;
 use chrono::{Local, TimeZone};
 use humantime::format_duration;
 use last_rs::{get_logins, Enter, Exit, LastError};
+use serde_json::json;
+use std::io::{self, Write};
 use std::time::Duration;
-use termion::{color, style};
+use termion::style;
 use thiserror::Error;
 use time::error::Format as TimeFormatError;
 use time::error::IndeterminateOffset as TimeIndeterminateOffsetError;
 use time::error::InvalidFormatDescription as TimeInvalidFormatDescriptionError;
 
 use crate::command::BetterCommandError;
-use crate::component::Component;
+use crate::component::{BoxedCollected, Collected, CollectedError, Component, ComponentError};
 use crate::config::global_config::GlobalConfig;
+use crate::config::theme::Level;
 use crate::constants::INDENT_WIDTH;
 use crate::default_prepare;
 
@@ -31,10 +34,14 @@ pub struct LastLogin {
 
 #[async_trait]
 impl Component for LastLogin {
-    async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
-        self.print_or_error(global_config)
-            .unwrap_or_else(|err| println!("Last login error: {err}"));
-        println!();
+    async fn collect(self: Box<Self>, global_config: &GlobalConfig) -> BoxedCollected {
+        match self.collect_or_error(global_config) {
+            Ok(collected) => Box::new(collected),
+            Err(err) => Box::new(CollectedError(ComponentError::new("last-login", err))),
+        }
+    }
+    fn name(&self) -> &'static str {
+        "last-login"
     }
     default_prepare!();
 }
@@ -66,7 +73,7 @@ pub enum LastLoginError {
 fn format_entry(
     entry: &Enter,
     longest_location: usize,
-    time_format: &str,
+    global_config: &GlobalConfig,
 ) -> Result<String, LastLoginError> {
     let location = format!("{:>width$}", entry.host, width = longest_location);
     let login_time = entry.login_time;
@@ -81,9 +88,9 @@ fn format_entry(
         }
         _ => {
             let (colour, message) = match entry.exit {
-                Exit::StillLoggedIn => (color::Fg(color::Green).to_string(), "still logged in"),
-                Exit::Crash(_) => (color::Fg(color::Yellow).to_string(), "crash"),
-                Exit::Reboot(_) => (color::Fg(color::Yellow).to_string(), "down"),
+                Exit::StillLoggedIn => (global_config.theme.level(Level::Ok), "still logged in"),
+                Exit::Crash(_) => (global_config.theme.level(Level::Warn), "crash"),
+                Exit::Reboot(_) => (global_config.theme.level(Level::Warn), "down"),
                 Exit::Logout(_) => unreachable!(),
             };
             format!("{}{}{}", colour, message, style::Reset)
@@ -98,51 +105,108 @@ fn format_entry(
         login_time = Local
             .timestamp_opt(login_time.unix_timestamp(), 0)
             .unwrap()
-            .format(time_format),
+            .format(&global_config.time_format),
         exit = exit,
         indent = " ".repeat(2 * INDENT_WIDTH),
     ))
 }
 
-impl LastLogin {
-    pub fn print_or_error(self, global_config: &GlobalConfig) -> Result<(), LastLoginError> {
-        println!("Last Login:");
-
-        for User {
-            username,
-            num_logins,
-        } in self.users
-        {
-            println!("{}{}:", " ".repeat(INDENT_WIDTH), username);
-            let entries = get_logins("/var/log/wtmp")?
-                .into_iter()
-                .filter(|entry| entry.user == username)
-                .take(num_logins)
-                .collect::<Vec<Enter>>();
+struct CollectedLastLogin {
+    /// The most recent logins of each user
+    logins: Vec<(String, Vec<Enter>)>,
+}
+
+impl Collected for CollectedLastLogin {
+    fn print(
+        self: Box<Self>,
+        global_config: &GlobalConfig,
+        _width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        writeln!(out, "Last Login:")?;
+
+        for (username, entries) in self.logins {
+            writeln!(out, "{}{}:", " ".repeat(INDENT_WIDTH), username)?;
 
             let longest_location = entries.iter().map(|entry| entry.host.len()).max();
             match longest_location {
                 Some(longest_location) => {
-                    let formatted_entries = entries.iter().map(|entry| {
-                        format_entry(entry, longest_location, &global_config.time_format)
-                    });
+                    let formatted_entries = entries
+                        .iter()
+                        .map(|entry| format_entry(entry, longest_location, global_config));
                     for entry in formatted_entries {
                         match entry {
-                            Ok(x) => println!("{x}"),
-                            Err(err) => println!("{err}"),
+                            Ok(x) => writeln!(out, "{x}")?,
+                            Err(err) => writeln!(out, "{err}")?,
                         }
                     }
                 }
-                None => println!(
+                None => writeln!(
+                    out,
                     "{indent}{color}No logins found for `{username}'{reset}",
                     indent = " ".repeat(2 * INDENT_WIDTH),
                     username = username,
-                    color = color::Fg(color::Red),
+                    color = global_config.theme.level(Level::Critical),
                     reset = style::Reset,
-                ),
+                )?,
             }
         }
+        writeln!(out)
+    }
 
-        Ok(())
+    fn to_json(&self) -> serde_json::Value {
+        let logins: serde_json::Map<_, _> = self
+            .logins
+            .iter()
+            .map(|(username, entries)| {
+                let entries = entries
+                    .iter()
+                    .map(|entry| {
+                        let (exit, exit_time) = match entry.exit {
+                            Exit::Logout(time) => ("logout", Some(time.unix_timestamp())),
+                            Exit::Crash(time) => ("crash", Some(time.unix_timestamp())),
+                            Exit::Reboot(time) => ("reboot", Some(time.unix_timestamp())),
+                            Exit::StillLoggedIn => ("still_logged_in", None),
+                        };
+                        json!({
+                            "host": entry.host,
+                            "login_time": entry.login_time.unix_timestamp(),
+                            "exit": exit,
+                            "exit_time": exit_time,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                (username.clone(), json!(entries))
+            })
+            .collect();
+        json!(logins)
+    }
+}
+
+impl LastLogin {
+    fn collect_or_error(
+        self,
+        global_config: &GlobalConfig,
+    ) -> Result<CollectedLastLogin, LastLoginError> {
+        let wtmp = global_config.sysroot.path("/var/log/wtmp");
+        let logins = self
+            .users
+            .into_iter()
+            .map(
+                |User {
+                     username,
+                     num_logins,
+                 }| {
+                    let entries = get_logins(&wtmp.to_string_lossy())?
+                        .into_iter()
+                        .filter(|entry| entry.user == username)
+                        .take(num_logins)
+                        .collect::<Vec<Enter>>();
+                    Ok((username, entries))
+                },
+            )
+            .collect::<Result<_, LastLoginError>>()?;
+
+        Ok(CollectedLastLogin { logins })
     }
 }
@@ -1,9 +1,10 @@
 use async_trait::async_trait;
-use chrono::Local;
+use chrono::{DateTime, Local};
 use serde::Deserialize;
-use thiserror::Error;
+use serde_json::json;
+use std::io::{self, Write};
 
-use crate::component::Component;
+use crate::component::{BoxedCollected, Collected, Component};
 use crate::config::global_config::GlobalConfig;
 use crate::default_prepare;
 
@@ -12,28 +13,34 @@ pub struct LastRun {}
 
 #[async_trait]
 impl Component for LastRun {
-    async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
-        self.print_or_error(global_config)
-            .unwrap_or_else(|err| println!("Last run error: {err}"));
+    async fn collect(self: Box<Self>, _global_config: &GlobalConfig) -> BoxedCollected {
+        Box::new(CollectedLastRun { time: Local::now() })
+    }
+    fn name(&self) -> &'static str {
+        "last-run"
     }
     default_prepare!();
 }
 
-#[derive(Error, Debug)]
-pub enum LastRunError {
-    #[error(transparent)]
-    ChronoParse(#[from] chrono::ParseError),
-
-    #[error(transparent)]
-    IO(#[from] std::io::Error),
+struct CollectedLastRun {
+    time: DateTime<Local>,
 }
 
-impl LastRun {
-    pub fn print_or_error(self, global_config: &GlobalConfig) -> Result<(), LastRunError> {
-        println!(
+impl Collected for CollectedLastRun {
+    fn print(
+        self: Box<Self>,
+        global_config: &GlobalConfig,
+        _width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        writeln!(
+            out,
             "Last updated: {}",
-            Local::now().format(&global_config.time_format)
-        );
-        Ok(())
+            self.time.format(&global_config.time_format)
+        )
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({ "time": self.time.to_rfc3339() })
     }
 }
@@ -1,16 +1,19 @@
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::Display;
-use std::thread::available_parallelism;
+use std::io::{self, Write};
 
 use async_trait::async_trait;
 use interpolator::Formattable;
 use serde::Deserialize;
-use systemstat::{Platform, System};
-use termion::{color, style};
+use serde_json::json;
+use termion::style;
 
-use crate::component::Component;
+use crate::component::{
+    BoxedCollected, Collected, CollectedError, Component, ComponentError, Issue, PerfData, Severity,
+};
 use crate::config::global_config::GlobalConfig;
+use crate::config::theme::{Level, Theme};
 use crate::default_prepare;
 
 #[derive(knus::Decode, Debug, Deserialize)]
@@ -25,10 +28,25 @@ pub struct LoadAvg {
 
 #[async_trait]
 impl Component for LoadAvg {
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
-        self.print_or_error()
-            .unwrap_or_else(|err| println!("LoadAvg error: {err}"));
-        println!();
+    async fn collect(self: Box<Self>, global_config: &GlobalConfig) -> BoxedCollected {
+        match self.collect_or_error(global_config) {
+            Ok(collected) => Box::new(collected),
+            Err(err) => Box::new(CollectedError(ComponentError::new("load-avg", err))),
+        }
+    }
+    fn name(&self) -> &'static str {
+        "load-avg"
+    }
+    async fn check(self: Box<Self>, _global_config: &GlobalConfig) -> Vec<String> {
+        let value = 0.0_f32;
+        let context: HashMap<_, _> = ["one", "five", "fifteen"]
+            .into_iter()
+            .map(|k| (k, Formattable::display(&value)))
+            .collect();
+        match interpolator::format(&self.format, &context) {
+            Ok(_) => vec![],
+            Err(err) => vec![format!("Invalid format {:?}: {err}", self.format)],
+        }
     }
     default_prepare!();
 }
@@ -39,27 +57,128 @@ struct LoadValue {
     bad: f32,
 }
 
-impl Display for LoadValue {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let color = if self.load >= self.bad {
-            color::Fg(color::Red).to_string()
+impl LoadValue {
+    fn level(&self) -> Level {
+        if self.load >= self.bad {
+            Level::Critical
         } else if self.load >= self.warn {
-            color::Fg(color::Yellow).to_string()
+            Level::Warn
         } else {
-            color::Fg(color::Green).to_string()
-        };
-        write!(f, "{color}")?;
-        self.load.fmt(f)?;
+            Level::Ok
+        }
+    }
+}
+
+/// A load value printed in the colour of its level
+struct ColoredLoadValue<'a> {
+    value: &'a LoadValue,
+    theme: &'a Theme,
+}
+
+impl Display for ColoredLoadValue<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.theme.level(self.value.level()))?;
+        self.value.load.fmt(f)?;
         write!(f, "{}", style::Reset)?;
         Ok(())
     }
 }
 
+struct CollectedLoadAvg {
+    format: String,
+    values: Vec<(&'static str, LoadValue)>,
+    warn: f32,
+    bad: f32,
+}
+
+impl Collected for CollectedLoadAvg {
+    fn print(
+        self: Box<Self>,
+        global_config: &GlobalConfig,
+        _width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let values: Vec<_> = self
+            .values
+            .iter()
+            .map(|(k, value)| {
+                (
+                    *k,
+                    ColoredLoadValue {
+                        value,
+                        theme: &global_config.theme,
+                    },
+                )
+            })
+            .collect();
+        // interpolator::Context holding references to values
+        let context: HashMap<_, _> = values
+            .iter()
+            .map(|(k, v)| (*k, Formattable::display(v)))
+            .collect();
+
+        match interpolator::format(&self.format, &context) {
+            Ok(formatted) => writeln!(out, "{formatted}")?,
+            Err(err) => writeln!(out, "LoadAvg error: {err}")?,
+        }
+        writeln!(out)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let load: serde_json::Map<_, _> = self
+            .values
+            .iter()
+            .map(|(k, v)| (k.to_string(), json!(v.load)))
+            .collect();
+        json!({
+            "load": load,
+            "warn_threshold": self.warn,
+            "bad_threshold": self.bad,
+        })
+    }
+
+    fn issues(&self) -> Vec<Issue> {
+        // Only the worst of the load averages is reported, the others are usually similar
+        let worst = self
+            .values
+            .iter()
+            .zip(["1 min", "5 min", "15 min"])
+            .map(|((_, value), period)| (Severity::from(value.level()), value.load, period))
+            .max_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        match worst {
+            Some((severity, load, period)) if severity != Severity::Ok => {
+                vec![Issue::new(severity, format!("load {load:.2} ({period})"))]
+            }
+            _ => vec![],
+        }
+    }
+
+    fn perfdata(&self) -> Vec<PerfData> {
+        // Load averages have two decimals, which are lost when converted to `f64` as is
+        let round = |value: f32| (f64::from(value) * 100.0).round() / 100.0;
+        ["load1", "load5", "load15"]
+            .into_iter()
+            .zip(&self.values)
+            .map(|(label, (_, value))| PerfData {
+                label: label.to_string(),
+                value: round(value.load),
+                unit: String::new(),
+                warn: Some(round(value.warn)),
+                critical: Some(round(value.bad)),
+                min: Some(0.0),
+                max: None,
+            })
+            .collect()
+    }
+}
+
 impl LoadAvg {
-    pub fn print_or_error(self) -> Result<(), Box<dyn Error>> {
-        let sys = System::new();
-        let lavg = sys.load_average()?;
-        let num_cpus = available_parallelism()?.get();
+    fn collect_or_error(
+        self,
+        global_config: &GlobalConfig,
+    ) -> Result<CollectedLoadAvg, Box<dyn Error + Send + Sync>> {
+        let lavg = global_config.sysroot.load_average()?;
+        let num_cpus = global_config.sysroot.cpus()?;
         let warn = self.warn_threshold.unwrap_or(num_cpus as f32);
         let bad = self.bad_threshold.unwrap_or((4 * num_cpus) as f32);
 
@@ -73,14 +192,11 @@ impl LoadAvg {
         .map(|(k, v)| (k, LoadValue { load: v, warn, bad }))
         .collect();
 
-        // interpolator::Context holding references to values
-        let context: HashMap<_, _> = values
-            .iter()
-            .map(|(k, v)| (*k, Formattable::display(v)))
-            .collect();
-
-        println!("{}", interpolator::format(&self.format, &context)?);
-
-        Ok(())
+        Ok(CollectedLoadAvg {
+            format: self.format,
+            values,
+            warn,
+            bad,
+        })
     }
 }
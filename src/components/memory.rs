@@ -1,26 +1,52 @@
 use async_trait::async_trait;
-use serde::Deserialize;
-use systemstat::{saturating_sub_bytes, Platform, System};
-use termion::{color, style};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::iter;
+use systemstat::{saturating_sub_bytes, ByteSize};
 use thiserror::Error;
 
-use crate::component::Component;
+use crate::component::{
+    BoxedCollected, Collected, CollectedError, Component, ComponentError, Issue, PerfData, Severity,
+};
 use crate::config::global_config::GlobalConfig;
+use crate::config::theme::{default_critical, default_warn, Level, DEFAULT_CRITICAL, DEFAULT_WARN};
 use crate::constants::INDENT_WIDTH;
 use crate::default_prepare;
+use crate::progress_bar::ProgressBar;
 
 #[derive(knus::Decode, Debug, Deserialize)]
 pub struct Memory {
     #[knus(property)]
     swap_pos: SwapPosition,
+
+    /// Percentage of usage above which the bars are printed in the warn colour
+    #[knus(property, default = DEFAULT_WARN)]
+    #[serde(default = "default_warn")]
+    warn: usize,
+
+    /// Percentage of usage above which the bars are printed in the critical colour
+    #[knus(property, default = DEFAULT_CRITICAL)]
+    #[serde(default = "default_critical")]
+    critical: usize,
 }
 
 #[async_trait]
 impl Component for Memory {
-    async fn print(self: Box<Self>, global_config: &GlobalConfig, width: Option<usize>) {
-        self.print_or_error(global_config, width)
-            .unwrap_or_else(|err| println!("Memory error: {err}"));
-        println!();
+    async fn collect(self: Box<Self>, global_config: &GlobalConfig) -> BoxedCollected {
+        match self.collect_or_error(global_config) {
+            Ok(collected) => Box::new(collected),
+            Err(err) => Box::new(CollectedError(ComponentError::new("memory", err))),
+        }
+    }
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+    async fn check(self: Box<Self>, _global_config: &GlobalConfig) -> Vec<String> {
+        Level::check_thresholds(self.warn, self.critical)
+            .into_iter()
+            .collect()
     }
     default_prepare!();
 }
@@ -49,141 +75,122 @@ enum SwapPosition {
     None,
 }
 
+#[derive(Serialize)]
 struct MemoryUsage {
     name: String,
+    #[serde(skip)]
     used: String,
+    #[serde(skip)]
     total: String,
+    used_bytes: u64,
+    total_bytes: u64,
     used_ratio: f64,
 }
 
 impl MemoryUsage {
-    #[cfg(any(target_os = "linux", target_os = "android"))]
     fn get_by_name(
         name: String,
-        sys: &System,
+        meminfo: &BTreeMap<String, ByteSize>,
         free_name: &str,
         total_name: &str,
     ) -> Result<Self, MemoryError> {
-        let memory = sys.memory()?;
-        let total =
-            memory
-                .platform_memory
-                .meminfo
-                .get(total_name)
-                .ok_or(MemoryError::MemoryNotFound {
-                    quantity: total_name.to_string(),
-                })?;
-
-        let free =
-            memory
-                .platform_memory
-                .meminfo
-                .get(free_name)
-                .ok_or(MemoryError::MemoryNotFound {
-                    quantity: free_name.to_string(),
-                })?;
+        let total = meminfo.get(total_name).ok_or(MemoryError::MemoryNotFound {
+            quantity: total_name.to_string(),
+        })?;
+
+        let free = meminfo.get(free_name).ok_or(MemoryError::MemoryNotFound {
+            quantity: free_name.to_string(),
+        })?;
         let used = saturating_sub_bytes(*total, *free);
         Ok(MemoryUsage {
             name,
             used: used.to_string(),
             total: total.to_string(),
+            used_bytes: used.as_u64(),
+            total_bytes: total.as_u64(),
             used_ratio: used.as_u64() as f64 / total.as_u64() as f64,
         })
     }
-
-    #[cfg(not(any(target_os = "linux", target_os = "android")))]
-    fn get_by_name(
-        name: String,
-        sys: &System,
-        free_name: &str,
-        total_name: &str,
-    ) -> Result<Self, MemoryError> {
-        Err(MemoryError::UnsupportedPlatform)
-    }
 }
 
-fn format_bar(
-    global_config: &GlobalConfig,
-    width: usize,
-    full_ratio: f64,
-    full_color: String,
-) -> String {
-    let without_ends_width =
-        width - global_config.progress_suffix.len() - global_config.progress_prefix.len();
-
-    let bar_full = ((without_ends_width as f64) * full_ratio) as usize;
-    let bar_empty = without_ends_width - bar_full;
-
-    [
-        global_config.progress_prefix.to_string(),
-        full_color,
-        global_config
-            .progress_full_character
-            .to_string()
-            .repeat(bar_full),
-        color::Fg(color::LightBlack).to_string(),
-        global_config
-            .progress_empty_character
-            .to_string()
-            .repeat(bar_empty),
-        style::Reset.to_string(),
-        global_config.progress_suffix.to_string(),
-    ]
-    .join("")
+/// The quantities of `/proc/meminfo`, like `MemTotal`
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn meminfo(global_config: &GlobalConfig) -> Result<BTreeMap<String, ByteSize>, MemoryError> {
+    Ok(global_config.sysroot.meminfo()?)
 }
 
-fn full_color(ratio: f64) -> String {
-    match (ratio * 100.) as usize {
-        0..=75 => color::Fg(color::Green).to_string(),
-        76..=95 => color::Fg(color::Yellow).to_string(),
-        _ => color::Fg(color::Red).to_string(),
-    }
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn meminfo(_global_config: &GlobalConfig) -> Result<BTreeMap<String, ByteSize>, MemoryError> {
+    Err(MemoryError::UnsupportedPlatform)
 }
 
-fn print_stacked(entries: Vec<MemoryUsage>, width: usize, global_config: &GlobalConfig) {
+fn print_stacked(
+    entries: Vec<MemoryUsage>,
+    width: usize,
+    (warn, critical): (usize, usize),
+    global_config: &GlobalConfig,
+    out: &mut dyn Write,
+) -> io::Result<()> {
     for entry in entries {
-        println!(
+        writeln!(
+            out,
             "{}{}: {} / {}",
             " ".repeat(INDENT_WIDTH),
             entry.name,
             entry.used,
             entry.total
-        );
-        let full_color = full_color(entry.used_ratio);
-        let bar = format_bar(global_config, width, entry.used_ratio, full_color);
-        println!(
+        )?;
+        let bar = ProgressBar {
+            ratio: entry.used_ratio,
+            warn,
+            critical,
+        }
+        .render(global_config, width);
+        writeln!(
+            out,
             "{indent}{bar}",
             indent = " ".repeat(INDENT_WIDTH),
             bar = bar
-        );
+        )?;
     }
+    Ok(())
 }
 
-impl Memory {
-    pub fn print_or_error(
-        self,
+#[derive(Serialize)]
+struct CollectedMemory {
+    #[serde(skip)]
+    swap_pos: SwapPosition,
+    #[serde(rename = "ram")]
+    ram_usage: MemoryUsage,
+    #[serde(rename = "swap")]
+    swap_usage: Option<MemoryUsage>,
+    #[serde(skip)]
+    warn: usize,
+    #[serde(skip)]
+    critical: usize,
+}
+
+impl Collected for CollectedMemory {
+    fn print(
+        self: Box<Self>,
         global_config: &GlobalConfig,
         width: Option<usize>,
-    ) -> Result<(), MemoryError> {
-        let sys = System::new();
-        let width = width.unwrap_or(global_config.progress_width - INDENT_WIDTH);
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let width = width.unwrap_or(
+            global_config
+                .progress_width
+                .fallback()
+                .saturating_sub(INDENT_WIDTH),
+        );
 
-        let ram_usage =
-            MemoryUsage::get_by_name("RAM".to_string(), &sys, "MemAvailable", "MemTotal")?;
-        println!("Memory");
-        match self.swap_pos {
-            SwapPosition::None => print_stacked(vec![ram_usage], width, global_config),
-            SwapPosition::Below => {
-                let swap_usage =
-                    MemoryUsage::get_by_name("Swap".to_string(), &sys, "SwapFree", "SwapTotal")?;
-                print_stacked(vec![ram_usage, swap_usage], width, global_config)
-            }
-            SwapPosition::Beside => {
-                let swap_usage =
-                    MemoryUsage::get_by_name("Swap".to_string(), &sys, "SwapFree", "SwapTotal")?;
+        writeln!(out, "Memory")?;
+        match (self.swap_pos, self.swap_usage) {
+            (SwapPosition::Beside, Some(swap_usage)) => {
+                let ram_usage = self.ram_usage;
 
                 let min_spacing = 1;
-                let bar_width = (width - min_spacing) / 2;
+                let bar_width = width.saturating_sub(min_spacing) / 2;
                 let spacing = width - 2 * bar_width;
                 let spacing = " ".repeat(spacing);
 
@@ -195,30 +202,107 @@ impl Memory {
                     "{}: {} / {}",
                     swap_usage.name, swap_usage.used, swap_usage.total
                 );
-                println!(
+                writeln!(
+                    out,
                     "{}{ram_label:padding$}{spacing}{swap_label}",
                     " ".repeat(INDENT_WIDTH),
                     ram_label = ram_label,
                     padding = bar_width,
                     spacing = spacing,
                     swap_label = swap_label
-                );
-                let bar_color = full_color(ram_usage.used_ratio);
-                let ram_bar = format_bar(global_config, bar_width, ram_usage.used_ratio, bar_color);
-
-                let bar_color = full_color(swap_usage.used_ratio);
-                let swap_bar =
-                    format_bar(global_config, bar_width, swap_usage.used_ratio, bar_color);
-                println!(
+                )?;
+                let ram_bar = ProgressBar {
+                    ratio: ram_usage.used_ratio,
+                    warn: self.warn,
+                    critical: self.critical,
+                }
+                .render(global_config, bar_width);
+                let swap_bar = ProgressBar {
+                    ratio: swap_usage.used_ratio,
+                    warn: self.warn,
+                    critical: self.critical,
+                }
+                .render(global_config, bar_width);
+                writeln!(
+                    out,
                     "{indent}{ram}{spacing}{swap}",
                     indent = " ".repeat(INDENT_WIDTH),
                     ram = ram_bar,
                     spacing = spacing,
                     swap = swap_bar
-                );
+                )?;
             }
+            (_, swap_usage) => print_stacked(
+                iter::once(self.ram_usage).chain(swap_usage).collect(),
+                width,
+                (self.warn, self.critical),
+                global_config,
+                out,
+            )?,
         }
+        writeln!(out)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!(self)
+    }
 
-        Ok(())
+    fn issues(&self) -> Vec<Issue> {
+        self.usages()
+            .filter_map(|usage| {
+                let severity = Level::from_usage(usage.used_ratio, self.warn, self.critical).into();
+                let message = format!("{} {:.0}% used", usage.name, usage.used_ratio * 100.0);
+                (severity != Severity::Ok).then(|| Issue::new(severity, message))
+            })
+            .collect()
+    }
+
+    fn perfdata(&self) -> Vec<PerfData> {
+        self.usages()
+            .map(|usage| {
+                PerfData::usage(
+                    &usage.name.to_lowercase(),
+                    usage.used_bytes,
+                    usage.total_bytes,
+                    self.warn,
+                    self.critical,
+                )
+            })
+            .collect()
+    }
+}
+
+impl CollectedMemory {
+    fn usages(&self) -> impl Iterator<Item = &MemoryUsage> {
+        iter::once(&self.ram_usage).chain(&self.swap_usage)
+    }
+}
+
+impl Memory {
+    fn collect_or_error(
+        self,
+        global_config: &GlobalConfig,
+    ) -> Result<CollectedMemory, MemoryError> {
+        let meminfo = meminfo(global_config)?;
+
+        let ram_usage =
+            MemoryUsage::get_by_name("RAM".to_string(), &meminfo, "MemAvailable", "MemTotal")?;
+        let swap_usage = match self.swap_pos {
+            SwapPosition::None => None,
+            SwapPosition::Below | SwapPosition::Beside => Some(MemoryUsage::get_by_name(
+                "Swap".to_string(),
+                &meminfo,
+                "SwapFree",
+                "SwapTotal",
+            )?),
+        };
+
+        Ok(CollectedMemory {
+            swap_pos: self.swap_pos,
+            ram_usage,
+            swap_usage,
+            warn: self.warn,
+            critical: self.critical,
+        })
     }
 }
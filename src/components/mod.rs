@@ -8,7 +8,9 @@ pub mod last_login;
 pub mod last_run;
 pub mod loadavg;
 pub mod memory;
+pub mod plugin;
 pub mod service_status;
 pub mod ssl_certs;
+pub mod summary;
 pub mod uptime;
 pub mod weather;
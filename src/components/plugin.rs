@@ -0,0 +1,449 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::env;
+use std::io::{self, Write};
+use std::path::Path;
+use termion::style;
+use thiserror::Error;
+
+use crate::command::{BetterCommand, BetterCommandError};
+use crate::component::{
+    BoxedCollected, Collected, CollectedError, Component, ComponentError, Issue, PerfData, Severity,
+};
+use crate::config::global_config::GlobalConfig;
+use crate::config::theme::{default_critical, default_warn, Level, Theme};
+use crate::constants::INDENT_WIDTH;
+use crate::default_prepare;
+use crate::progress_bar::ProgressBar;
+
+/// Version of the protocol spoken with plugins
+/// It is sent to the plugin, which must reply with the same version
+const PROTOCOL_VERSION: u32 = 1;
+
+/// Runs an external executable, which replies with the content of the component in JSON
+/// and leaves the rendering (alignment, bars, colours) to `rust-motd`
+#[derive(knus::Decode, Debug)]
+pub struct Plugin {
+    #[knus(argument)]
+    executable: String,
+    #[knus(arguments)]
+    args: Vec<String>,
+    /// Sent to the plugin as JSON, with the types they have in KDL
+    #[knus(properties)]
+    options: BTreeMap<String, OptionValue>,
+}
+
+/// The value of an option of a plugin: a string, a number, a boolean or null
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct OptionValue(serde_json::Value);
+
+impl<S: knus::traits::ErrorSpan> knus::DecodeScalar<S> for OptionValue {
+    fn type_check(
+        _type_name: &Option<knus::span::Spanned<knus::ast::TypeName, S>>,
+        _ctx: &mut knus::decode::Context<S>,
+    ) {
+    }
+
+    fn raw_decode(
+        value: &knus::span::Spanned<knus::ast::Literal, S>,
+        ctx: &mut knus::decode::Context<S>,
+    ) -> Result<Self, knus::errors::DecodeError<S>> {
+        let json = match &**value {
+            knus::ast::Literal::Null => serde_json::Value::Null,
+            knus::ast::Literal::Bool(value) => json!(value),
+            knus::ast::Literal::String(value) => json!(value),
+            knus::ast::Literal::Int(_) => {
+                json!(<i64 as knus::DecodeScalar<S>>::raw_decode(value, ctx)?)
+            }
+            knus::ast::Literal::Decimal(_) => {
+                json!(<f64 as knus::DecodeScalar<S>>::raw_decode(value, ctx)?)
+            }
+        };
+        Ok(OptionValue(json))
+    }
+}
+
+#[async_trait]
+impl Component for Plugin {
+    async fn collect(self: Box<Self>, _global_config: &GlobalConfig) -> BoxedCollected {
+        match self.collect_or_error() {
+            Ok(collected) => Box::new(collected),
+            Err(err) => Box::new(CollectedError(ComponentError::new("plugin", err))),
+        }
+    }
+    fn name(&self) -> &'static str {
+        "plugin"
+    }
+    async fn check(self: Box<Self>, _global_config: &GlobalConfig) -> Vec<String> {
+        let found = match self.executable.contains('/') {
+            true => Path::new(&self.executable).is_file(),
+            false => env::var_os("PATH").is_some_and(|path| {
+                env::split_paths(&path).any(|dir| dir.join(&self.executable).is_file())
+            }),
+        };
+        match found {
+            true => vec![],
+            false => vec![format!("Plugin {} not found", self.executable)],
+        }
+    }
+    default_prepare!();
+}
+
+#[derive(Error, Debug)]
+pub enum PluginError {
+    #[error(transparent)]
+    Command(#[from] BetterCommandError),
+
+    #[error("Invalid reply from the plugin: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("The plugin uses version {0} of the protocol, but only version {PROTOCOL_VERSION} is supported")]
+    UnsupportedVersion(u32),
+}
+
+/// Written to the standard input of the plugin
+#[derive(Serialize)]
+struct Request<'a> {
+    version: u32,
+    options: &'a BTreeMap<String, OptionValue>,
+}
+
+/// Read from the standard output of the plugin
+#[derive(Deserialize, Serialize)]
+struct Reply {
+    version: u32,
+    /// Printed above the items, which are then indented
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    /// The severity of the whole plugin, in addition to the severities of the items
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    severity: Option<Severity>,
+    /// Describes the problem when `severity` is set (e.g. in the `summary` component)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(default)]
+    items: Vec<Item>,
+}
+
+/// A part of the output of a plugin, printed in the colour of its severity (if it has one)
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Item {
+    /// A line of text
+    Text {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        severity: Option<Severity>,
+    },
+    /// A key and a value, the values of all the pairs are aligned
+    Pair {
+        key: String,
+        value: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        severity: Option<Severity>,
+    },
+    /// A row of a table, the cells of all the rows are aligned
+    Row {
+        cells: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        severity: Option<Severity>,
+    },
+    /// A progress bar, coloured according to the warn and critical percentages
+    Bar {
+        label: String,
+        #[serde(default)]
+        text: String,
+        ratio: f64,
+        #[serde(default = "default_warn")]
+        warn: usize,
+        #[serde(default = "default_critical")]
+        critical: usize,
+    },
+}
+
+impl Item {
+    fn severity(&self) -> Option<Severity> {
+        match self {
+            Item::Text { severity, .. }
+            | Item::Pair { severity, .. }
+            | Item::Row { severity, .. } => *severity,
+            Item::Bar {
+                ratio,
+                warn,
+                critical,
+                ..
+            } => Some(Level::from_usage(*ratio, *warn, *critical).into()),
+        }
+    }
+
+    /// A description of the item that makes sense on its own
+    fn message(&self) -> String {
+        match self {
+            Item::Text { text, .. } => text.clone(),
+            Item::Pair { key, value, .. } => format!("{key} {value}"),
+            Item::Row { cells, .. } => cells.join(" "),
+            Item::Bar { label, ratio, .. } => format!("{label} {:.0}%", ratio * 100.0),
+        }
+    }
+}
+
+/// The escape sequence for the colour of a severity, or nothing if there is no severity
+fn severity_color(theme: &Theme, severity: Option<Severity>) -> String {
+    severity
+        .map(|severity| theme.level(severity.level()))
+        .unwrap_or_default()
+}
+
+struct CollectedPlugin {
+    executable: String,
+    reply: Reply,
+}
+
+impl Collected for CollectedPlugin {
+    fn print(
+        self: Box<Self>,
+        global_config: &GlobalConfig,
+        width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let reply = self.reply;
+        let indent = match &reply.title {
+            Some(title) => {
+                writeln!(out, "{title}:")?;
+                " ".repeat(INDENT_WIDTH)
+            }
+            None => String::new(),
+        };
+        let width = width.unwrap_or(
+            global_config
+                .progress_width
+                .fallback()
+                .saturating_sub(INDENT_WIDTH),
+        );
+        let theme = &global_config.theme;
+
+        let key_width = reply
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Pair { key, .. } => Some(key.len()),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        let mut cell_widths: Vec<usize> = vec![];
+        for item in &reply.items {
+            if let Item::Row { cells, .. } = item {
+                cell_widths.resize(cell_widths.len().max(cells.len()), 0);
+                for (cell, cell_width) in cells.iter().zip(&mut cell_widths) {
+                    *cell_width = (*cell_width).max(cell.len());
+                }
+            }
+        }
+
+        for item in reply.items {
+            let color = severity_color(theme, item.severity());
+            match item {
+                Item::Text { text, .. } => writeln!(out, "{indent}{color}{text}{}", style::Reset)?,
+                Item::Pair { key, value, .. } => writeln!(
+                    out,
+                    "{indent}{key}: {}{color}{value}{}",
+                    " ".repeat(key_width - key.len()),
+                    style::Reset
+                )?,
+                Item::Row { cells, .. } => {
+                    let row = cells
+                        .iter()
+                        .zip(&cell_widths)
+                        .map(|(cell, width)| format!("{cell:<width$}"))
+                        .collect::<Vec<_>>()
+                        .join(&" ".repeat(INDENT_WIDTH));
+                    writeln!(out, "{indent}{color}{}{}", row.trim_end(), style::Reset)?
+                }
+                Item::Bar {
+                    label,
+                    text,
+                    ratio,
+                    warn,
+                    critical,
+                } => {
+                    writeln!(out, "{indent}{label}: {text}")?;
+                    let bar = ProgressBar {
+                        ratio,
+                        warn,
+                        critical,
+                    }
+                    .render(global_config, width);
+                    writeln!(out, "{indent}{bar}")?;
+                }
+            }
+        }
+        writeln!(out)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!(self.reply)
+    }
+
+    fn issues(&self) -> Vec<Issue> {
+        let reply = &self.reply;
+        let prefix = reply.title.as_deref().unwrap_or(&self.executable);
+        let mut issues: Vec<_> = reply
+            .items
+            .iter()
+            .filter_map(|item| {
+                let severity = item
+                    .severity()
+                    .filter(|&severity| severity != Severity::Ok)?;
+                Some(Issue::new(
+                    severity,
+                    format!("{prefix}: {}", item.message()),
+                ))
+            })
+            .collect();
+        // The severity of the whole plugin is only reported if the items do not explain it
+        let worst = issues.iter().map(|issue| issue.severity).max();
+        if let Some(severity) = reply.severity {
+            if severity != Severity::Ok && worst.is_none_or(|worst| worst < severity) {
+                let message = match &reply.message {
+                    Some(message) => format!("{prefix}: {message}"),
+                    None => format!("{prefix} {}", severity.plugin_state()),
+                };
+                issues.push(Issue::new(severity, message));
+            }
+        }
+        issues
+    }
+
+    fn perfdata(&self) -> Vec<PerfData> {
+        self.reply
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Bar {
+                    label,
+                    ratio,
+                    warn,
+                    critical,
+                    ..
+                } => Some(PerfData {
+                    label: label.clone(),
+                    value: (ratio * 1000.0).round() / 10.0,
+                    unit: "%".to_string(),
+                    warn: Some(*warn as f64),
+                    critical: Some(*critical as f64),
+                    min: Some(0.0),
+                    max: Some(100.0),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Parse the reply of a plugin, checking the version of the protocol first
+/// so that a plugin speaking another version gets a clear error
+fn parse_reply(output: &str) -> Result<Reply, PluginError> {
+    #[derive(Deserialize)]
+    struct Version {
+        version: u32,
+    }
+    let Version { version } = serde_json::from_str(output)?;
+    if version != PROTOCOL_VERSION {
+        return Err(PluginError::UnsupportedVersion(version));
+    }
+    Ok(serde_json::from_str(output)?)
+}
+
+impl Plugin {
+    fn collect_or_error(self) -> Result<CollectedPlugin, PluginError> {
+        let request = Request {
+            version: PROTOCOL_VERSION,
+            options: &self.options,
+        };
+        let output = BetterCommand::new(&self.executable)
+            .args(&self.args)
+            .stdin(json!(request).to_string())
+            .check_status_and_get_output_string()?;
+
+        Ok(CollectedPlugin {
+            executable: self.executable,
+            reply: parse_reply(&output)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options() {
+        let plugin: Plugin = knus::parse::<Vec<Plugin>>(
+            "test.kdl",
+            r#"plugin "backups" count=5 ratio=0.5 verbose=true name="home" none=null"#,
+        )
+        .unwrap()
+        .remove(0);
+        let request = Request {
+            version: PROTOCOL_VERSION,
+            options: &plugin.options,
+        };
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "version": 1,
+                "options": { "count": 5, "ratio": 0.5, "verbose": true, "name": "home", "none": null }
+            })
+        );
+    }
+
+    #[test]
+    fn test_print_reply() {
+        let reply = parse_reply(
+            r#"{
+                "version": 1,
+                "title": "Backups",
+                "items": [
+                    { "type": "pair", "key": "Last run", "value": "2h ago" },
+                    { "type": "pair", "key": "Status", "value": "failed", "severity": "critical" },
+                    { "type": "row", "cells": ["home", "12 GB"] },
+                    { "type": "row", "cells": ["database", "3 GB"] },
+                    { "type": "bar", "label": "Disk", "text": "80 GB / 100 GB", "ratio": 0.8 }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let collected = Box::new(CollectedPlugin {
+            executable: "backups".to_string(),
+            reply,
+        });
+        let issues = collected.issues();
+        let messages: Vec<_> = issues.iter().map(|issue| &*issue.message).collect();
+        assert_eq!(messages, ["Backups: Status failed", "Backups: Disk 80%"]);
+
+        let mut out = vec![];
+        let mut writer = crate::output::StripColors::new(&mut out);
+        collected
+            .print(&GlobalConfig::default(), Some(12), &mut writer)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Backups:\n  \
+            Last run: 2h ago\n  \
+            Status:   failed\n  \
+            home      12 GB\n  \
+            database  3 GB\n  \
+            Disk: 80 GB / 100 GB\n  \
+            [==========]\n\n"
+        );
+
+        assert!(matches!(
+            parse_reply(r#"{ "version": 2 }"#),
+            Err(PluginError::UnsupportedVersion(2))
+        ));
+    }
+}
@@ -1,10 +1,18 @@
 use async_trait::async_trait;
-use termion::{color, style};
+use serde::Serialize;
+use serde_json::json;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use termion::style;
 use thiserror::Error;
 
 use crate::command::{BetterCommand, BetterCommandError};
-use crate::component::Component;
+use crate::component::{
+    BoxedCollected, Collected, CollectedError, Component, ComponentError, Issue, Severity,
+};
 use crate::config::global_config::GlobalConfig;
+use crate::config::theme::Level;
 use crate::constants::INDENT_WIDTH;
 use crate::default_prepare;
 
@@ -30,22 +38,38 @@ pub struct UserServiceStatus {
 
 #[async_trait]
 impl Component for ServiceStatus {
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
-        println!("System Services:");
-        print_or_error(&self.services, false)
-            .unwrap_or_else(|err| println!("Service status error: {err}"));
-        println!();
+    async fn collect(self: Box<Self>, _global_config: &GlobalConfig) -> BoxedCollected {
+        match collect_or_error(self.services, false) {
+            Ok(collected) => Box::new(collected),
+            Err(err) => Box::new(CollectedError(
+                ComponentError::new("service-status", err).with_title("System Services:"),
+            )),
+        }
+    }
+    fn name(&self) -> &'static str {
+        "service-status"
+    }
+    async fn check(self: Box<Self>, _global_config: &GlobalConfig) -> Vec<String> {
+        check_units(&self.services, false)
     }
     default_prepare!();
 }
 
 #[async_trait]
 impl Component for UserServiceStatus {
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
-        println!("User Services:");
-        print_or_error(&self.services, true)
-            .unwrap_or_else(|err| println!("User service status error: {err}"));
-        println!();
+    async fn collect(self: Box<Self>, _global_config: &GlobalConfig) -> BoxedCollected {
+        match collect_or_error(self.services, true) {
+            Ok(collected) => Box::new(collected),
+            Err(err) => Box::new(CollectedError(
+                ComponentError::new("user-service-status", err).with_title("User Services:"),
+            )),
+        }
+    }
+    fn name(&self) -> &'static str {
+        "user-service-status"
+    }
+    async fn check(self: Box<Self>, _global_config: &GlobalConfig) -> Vec<String> {
+        check_units(&self.services, true)
     }
     default_prepare!();
 }
@@ -62,52 +86,158 @@ pub enum ServiceStatusError {
     IO(#[from] std::io::Error),
 }
 
-fn get_service_status(service: &str, user: bool) -> Result<String, ServiceStatusError> {
-    let executable = "systemctl";
-    let mut args = vec!["is-active", service];
-
+/// Whether a systemd unit exists (is installed, whether or not it is enabled)
+pub fn unit_exists(unit: &str, user: bool) -> bool {
+    let mut command = Command::new("systemctl");
     if user {
-        args.insert(0, "--user");
+        command.arg("--user");
     }
+    command
+        .args(["cat", "--", unit])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+fn check_units(services: &[Service], user: bool) -> Vec<String> {
+    services
+        .iter()
+        .filter(|service| !unit_exists(&service.unit, user))
+        .map(|service| format!("Unit {} does not exist", service.unit))
+        .collect()
+}
+
+/// Query the status of each unit with its own `systemctl is-active` call, all at the same time
+/// (with several units, `systemctl is-active` prints a line per unit but nothing that ties
+/// a line to its unit)
+fn get_service_statuses(units: &[&str], user: bool) -> Result<Vec<String>, ServiceStatusError> {
+    thread::scope(|scope| {
+        let handles: Vec<_> = units
+            .iter()
+            .map(|unit| scope.spawn(move || get_service_status(unit, user)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
 
-    let output = BetterCommand::new(executable)
-        .args(args)
+fn get_service_status(unit: &str, user: bool) -> Result<String, ServiceStatusError> {
+    let mut command = BetterCommand::new("systemctl");
+    if user {
+        command.arg("--user");
+    }
+    let output = command
+        .args(["is-active", "--", unit])
         .get_output_string()?;
+    Ok(match output.trim() {
+        "" => String::from("unknown"),
+        status => status.to_string(),
+    })
+}
+
+#[derive(Serialize)]
+struct ServiceState {
+    display_name: String,
+    status: String,
+}
 
-    Ok(output.split_whitespace().collect())
+#[derive(Serialize)]
+struct CollectedServiceStatus {
+    user: bool,
+    services: Vec<ServiceState>,
 }
 
-pub fn print_or_error(config: &[Service], user: bool) -> Result<(), ServiceStatusError> {
+impl Collected for CollectedServiceStatus {
+    fn print(
+        self: Box<Self>,
+        global_config: &GlobalConfig,
+        _width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        match self.user {
+            true => writeln!(out, "User Services:")?,
+            false => writeln!(out, "System Services:")?,
+        }
+
+        let padding = self
+            .services
+            .iter()
+            .map(|service| service.display_name.len())
+            .max()
+            .unwrap_or(0);
+
+        for ServiceState {
+            display_name,
+            status,
+        } in self.services
+        {
+            let status_color = match status_level(&status) {
+                Some(level) => global_config.theme.level(level),
+                None => style::Reset.to_string(),
+            };
+
+            writeln!(
+                out,
+                "{}{}: {}{}{}{}",
+                " ".repeat(INDENT_WIDTH),
+                display_name,
+                " ".repeat(padding - display_name.len()),
+                status_color,
+                status,
+                style::Reset,
+            )?;
+        }
+        writeln!(out)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!(self)
+    }
+
+    fn issues(&self) -> Vec<Issue> {
+        self.services
+            .iter()
+            .filter_map(|service| {
+                let severity = status_level(&service.status)?.into();
+                let message = format!("{} {}", service.display_name, service.status);
+                (severity != Severity::Ok).then(|| Issue::new(severity, message))
+            })
+            .collect()
+    }
+}
+
+/// The level of the active state of a unit, if it is one that has a colour
+fn status_level(status: &str) -> Option<Level> {
+    match status {
+        "active" => Some(Level::Ok),
+        "inactive" => Some(Level::Warn),
+        "failed" => Some(Level::Critical),
+        _ => None,
+    }
+}
+
+fn collect_or_error(
+    config: Vec<Service>,
+    user: bool,
+) -> Result<CollectedServiceStatus, ServiceStatusError> {
     if config.is_empty() {
         return Err(ServiceStatusError::ConfigEmpty);
     }
 
-    let padding = config
-        .iter()
-        .map(|service| service.display_name.len())
-        .max()
-        .unwrap();
-
-    for Service { display_name, unit } in config.iter() {
-        let status = get_service_status(unit, user)?;
-
-        let status_color = match status.as_ref() {
-            "active" => color::Fg(color::Green).to_string(),
-            "inactive" => color::Fg(color::Yellow).to_string(),
-            "failed" => color::Fg(color::Red).to_string(),
-            _ => style::Reset.to_string(),
-        };
-
-        println!(
-            "{}{}: {}{}{}{}",
-            " ".repeat(INDENT_WIDTH),
+    let units: Vec<&str> = config.iter().map(|service| service.unit.as_str()).collect();
+    let statuses = get_service_statuses(&units, user)?;
+
+    let services = config
+        .into_iter()
+        .zip(statuses)
+        .map(|(Service { display_name, .. }, status)| ServiceState {
             display_name,
-            " ".repeat(padding - display_name.len()),
-            status_color,
             status,
-            style::Reset,
-        );
-    }
+        })
+        .collect();
 
-    Ok(())
+    Ok(CollectedServiceStatus { user, services })
 }
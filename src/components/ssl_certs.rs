@@ -2,19 +2,27 @@ use async_trait::async_trait;
 use chrono::{DateTime, Duration, Utc};
 use openssl::asn1::Asn1Time;
 use openssl::x509::X509;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use std::fs::File;
-use std::io::{BufReader, Read};
-use termion::{color, style};
+use std::io::{self, BufReader, Read, Write};
+use termion::style;
 use thiserror::Error;
 
-use crate::component::Component;
+use crate::component::{
+    BoxedCollected, Collected, CollectedError, Component, ComponentError, Issue, Severity,
+};
 use crate::config::global_config::GlobalConfig;
+use crate::config::theme::Level;
 use crate::constants::INDENT_WIDTH;
 use crate::default_prepare;
 
 const SECS_PER_DAY: i64 = 24 * 60 * 60;
 
+fn default_warn_days() -> i64 {
+    30
+}
+
 #[derive(knus::DecodeScalar, Debug, Deserialize, Default)]
 enum SortMethod {
     #[serde(alias = "alphabetical")] // Alias used to match lowercase spelling as well
@@ -39,6 +47,10 @@ pub struct SSLCerts {
     #[serde(default)]
     #[knus(property, default)]
     sort_method: SortMethod,
+    /// Number of days before the expiration from which a certificate is shown as expiring
+    #[serde(default = "default_warn_days")]
+    #[knus(property, default = default_warn_days())]
+    warn_days: i64,
     #[knus(children(name = "cert"))]
     #[serde(deserialize_with = "crate::config::toml_config::deserialize_certs")]
     certs: Vec<Cert>,
@@ -46,10 +58,26 @@ pub struct SSLCerts {
 
 #[async_trait]
 impl Component for SSLCerts {
-    async fn print(self: Box<Self>, global_config: &GlobalConfig, _width: Option<usize>) {
-        self.print_or_error(global_config)
-            .unwrap_or_else(|err| println!("SSL Certificate error: {err}"));
-        println!();
+    async fn collect(self: Box<Self>, global_config: &GlobalConfig) -> BoxedCollected {
+        match self.collect_or_error(global_config) {
+            Ok(collected) => Box::new(collected),
+            Err(err) => Box::new(CollectedError(ComponentError::new("ssl-certs", err))),
+        }
+    }
+    fn name(&self) -> &'static str {
+        "ssl-certs"
+    }
+    async fn check(self: Box<Self>, global_config: &GlobalConfig) -> Vec<String> {
+        self.certs
+            .iter()
+            .filter_map(|Cert { name, path }| {
+                let err = match std::fs::read(global_config.sysroot.path(path)) {
+                    Ok(cert) => X509::from_pem(&cert).err()?.to_string(),
+                    Err(err) => err.to_string(),
+                };
+                Some(format!("Certificate {name} ({path}): {err}"))
+            })
+            .collect()
     }
     default_prepare!();
 }
@@ -63,19 +91,105 @@ pub enum SSLCertsError {
     ErrorStack(#[from] openssl::error::ErrorStack),
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum CertStatus {
+    Expired,
+    Expiring,
+    Valid,
+}
+
+#[derive(Serialize)]
 struct CertInfo {
     name: String,
-    status: String,
+    status: CertStatus,
     expiration: DateTime<Utc>,
 }
 
+#[derive(Serialize)]
+struct CollectedSSLCerts {
+    #[serde(rename = "certs")]
+    cert_infos: Vec<CertInfo>,
+}
+
+impl Collected for CollectedSSLCerts {
+    fn print(
+        self: Box<Self>,
+        global_config: &GlobalConfig,
+        _width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        writeln!(out, "SSL Certificates:")?;
+        for cert_info in self.cert_infos.into_iter() {
+            let status = match cert_info.status {
+                CertStatus::Expired => {
+                    format!(
+                        "{}expired on{}",
+                        global_config.theme.level(Level::Critical),
+                        style::Reset
+                    )
+                }
+                CertStatus::Expiring => {
+                    format!(
+                        "{}expiring on{}",
+                        global_config.theme.level(Level::Warn),
+                        style::Reset
+                    )
+                }
+                CertStatus::Valid => {
+                    format!(
+                        "{}valid until{}",
+                        global_config.theme.level(Level::Ok),
+                        style::Reset
+                    )
+                }
+            };
+            writeln!(
+                out,
+                "{}{} {} {}",
+                " ".repeat(INDENT_WIDTH),
+                cert_info.name,
+                status,
+                cert_info.expiration.format(&global_config.time_format)
+            )?;
+        }
+        writeln!(out)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!(self)
+    }
+
+    fn issues(&self) -> Vec<Issue> {
+        self.cert_infos
+            .iter()
+            .filter_map(|cert_info| match cert_info.status {
+                CertStatus::Expired => Some(Issue::new(
+                    Severity::Critical,
+                    format!("cert {} expired", cert_info.name),
+                )),
+                CertStatus::Expiring => {
+                    let days = (cert_info.expiration - Utc::now()).num_days();
+                    Some(Issue::new(
+                        Severity::Warn,
+                        format!("cert {} expires in {days} days", cert_info.name),
+                    ))
+                }
+                CertStatus::Valid => None,
+            })
+            .collect()
+    }
+}
+
 impl SSLCerts {
-    pub fn print_or_error(self, global_config: &GlobalConfig) -> Result<(), SSLCertsError> {
+    fn collect_or_error(
+        self,
+        global_config: &GlobalConfig,
+    ) -> Result<CollectedSSLCerts, SSLCertsError> {
         let mut cert_infos: Vec<CertInfo> = Vec::new();
 
-        println!("SSL Certificates:");
         for Cert { name, path } in self.certs {
-            let cert = File::open(&path)?;
+            let cert = File::open(global_config.sysroot.path(&path))?;
             let cert = BufReader::new(cert);
             let cert: Vec<u8> = cert.bytes().collect::<Result<_, _>>()?;
             let cert = X509::from_pem(&cert)?;
@@ -86,11 +200,11 @@ impl SSLCerts {
 
             let now = Utc::now();
             let status = if expiration < now {
-                format!("{}expired on{}", color::Fg(color::Red), style::Reset)
-            } else if expiration < now + Duration::days(30) {
-                format!("{}expiring on{}", color::Fg(color::Yellow), style::Reset)
+                CertStatus::Expired
+            } else if expiration < now + Duration::days(self.warn_days) {
+                CertStatus::Expiring
             } else {
-                format!("{}valid until{}", color::Fg(color::Green), style::Reset)
+                CertStatus::Valid
             };
             cert_infos.push(CertInfo {
                 name,
@@ -104,21 +218,11 @@ impl SSLCerts {
                 cert_infos.sort_by(|a, b| a.name.cmp(&b.name));
             }
             SortMethod::Expiration => {
-                cert_infos.sort_by(|a, b| a.expiration.cmp(&b.expiration));
+                cert_infos.sort_by_key(|cert_info| cert_info.expiration);
             }
             SortMethod::Manual => {}
         }
 
-        for cert_info in cert_infos.into_iter() {
-            println!(
-                "{}{} {} {}",
-                " ".repeat(INDENT_WIDTH),
-                cert_info.name,
-                cert_info.status,
-                cert_info.expiration.format(&global_config.time_format)
-            );
-        }
-
-        Ok(())
+        Ok(CollectedSSLCerts { cert_infos })
     }
 }
@@ -0,0 +1,141 @@
+use async_trait::async_trait;
+use serde_json::json;
+use std::io::{self, Write};
+use termion::style;
+use unicode_ellipsis::truncate_str;
+
+use crate::component::{BoxedCollected, Collected, Component, Issue, Severity};
+use crate::config::global_config::GlobalConfig;
+use crate::config::theme::Level;
+use crate::default_prepare;
+
+/// A line listing the problems found by all the other components
+/// It is printed where it is placed in the configuration (usually first),
+/// but only once all the other components have been collected
+#[derive(knus::Decode, Debug)]
+pub struct Summary {
+    /// Print a line saying that everything is fine when there are no problems
+    #[knus(property, default = true)]
+    show_ok: bool,
+}
+
+#[async_trait]
+impl Component for Summary {
+    async fn collect(self: Box<Self>, _global_config: &GlobalConfig) -> BoxedCollected {
+        Box::new(CollectedSummary {
+            show_ok: self.show_ok,
+            issues: vec![],
+        })
+    }
+    fn name(&self) -> &'static str {
+        "summary"
+    }
+    default_prepare!();
+}
+
+struct CollectedSummary {
+    show_ok: bool,
+    /// The issues of all the components, worst first
+    issues: Vec<Issue>,
+}
+
+impl CollectedSummary {
+    /// The number of issues of each severity, like `1 critical, 2 warnings`
+    fn counts(&self) -> String {
+        [
+            (Severity::Unknown, "error", "errors"),
+            (Severity::Critical, "critical", "critical"),
+            (Severity::Warn, "warning", "warnings"),
+        ]
+        .into_iter()
+        .filter_map(|(severity, singular, plural)| {
+            match self
+                .issues
+                .iter()
+                .filter(|issue| issue.severity == severity)
+                .count()
+            {
+                0 => None,
+                1 => Some(format!("1 {singular}")),
+                count => Some(format!("{count} {plural}")),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+impl Collected for CollectedSummary {
+    fn print(
+        self: Box<Self>,
+        global_config: &GlobalConfig,
+        width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let (level, text) = match self.issues.first() {
+            None if !self.show_ok => return Ok(()),
+            None => (Level::Ok, "No problems found".to_string()),
+            Some(worst) => {
+                let messages: Vec<_> = self.issues.iter().map(|issue| &*issue.message).collect();
+                let counts = self.counts();
+                (
+                    worst.severity.level(),
+                    format!("{counts}: {}", messages.join(", ")),
+                )
+            }
+        };
+        let text = match width {
+            Some(width) => truncate_str(&text, width),
+            None => text.as_str().into(),
+        };
+        writeln!(
+            out,
+            "{}{text}{}",
+            global_config.theme.level(level),
+            style::Reset
+        )?;
+        writeln!(out)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({ "issues": self.issues })
+    }
+
+    fn summarize(&mut self, issues: &[Issue]) {
+        self.issues = issues.to_vec();
+        self.issues
+            .sort_by_key(|issue| std::cmp::Reverse(issue.severity));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let mut summary = Box::new(CollectedSummary {
+            show_ok: false,
+            issues: vec![],
+        });
+        summary.summarize(&[
+            Issue::new(Severity::Warn, "/var 91% full".to_string()),
+            Issue::new(Severity::Critical, "nginx failed".to_string()),
+            Issue::new(
+                Severity::Warn,
+                "cert example.com expires in 5 days".to_string(),
+            ),
+        ]);
+        assert_eq!(summary.counts(), "1 critical, 2 warnings");
+
+        let mut out = vec![];
+        let mut writer = crate::output::StripColors::new(&mut out);
+        summary
+            .print(&GlobalConfig::default(), Some(40), &mut writer)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1 critical, 2 warnings: nginx failed, /…\n\n"
+        );
+    }
+}
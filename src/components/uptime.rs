@@ -1,9 +1,11 @@
 use async_trait::async_trait;
 use humantime::format_duration;
 use serde::Deserialize;
-use systemstat::{Platform, System};
+use serde_json::json;
+use std::io::{self, Write};
+use std::time::Duration;
 
-use crate::component::Component;
+use crate::component::{BoxedCollected, Collected, CollectedError, Component, ComponentError};
 use crate::config::global_config::GlobalConfig;
 use crate::default_prepare;
 
@@ -15,20 +17,49 @@ pub struct Uptime {
 
 #[async_trait]
 impl Component for Uptime {
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
-        self.print_or_error()
-            .unwrap_or_else(|err| println!("Uptime error: {err}"));
-        println!();
+    async fn collect(self: Box<Self>, global_config: &GlobalConfig) -> BoxedCollected {
+        match self.collect_or_error(global_config) {
+            Ok(collected) => Box::new(collected),
+            Err(err) => Box::new(CollectedError(ComponentError::new("uptime", err))),
+        }
+    }
+    fn name(&self) -> &'static str {
+        "uptime"
     }
     default_prepare!();
 }
 
+struct CollectedUptime {
+    prefix: String,
+    uptime: Duration,
+}
+
+impl Collected for CollectedUptime {
+    fn print(
+        self: Box<Self>,
+        _global_config: &GlobalConfig,
+        _width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        writeln!(out, "{} {}", self.prefix, format_duration(self.uptime))?;
+        writeln!(out)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({ "uptime_secs": self.uptime.as_secs() })
+    }
+}
+
 impl Uptime {
-    pub fn print_or_error(self) -> Result<(), std::io::Error> {
-        let sys = System::new();
-        let uptime = sys.uptime()?;
-        println!("{} {}", self.prefix, format_duration(uptime));
+    fn collect_or_error(
+        self,
+        global_config: &GlobalConfig,
+    ) -> Result<CollectedUptime, std::io::Error> {
+        let uptime = global_config.sysroot.uptime()?;
 
-        Ok(())
+        Ok(CollectedUptime {
+            prefix: self.prefix,
+            uptime,
+        })
     }
 }
@@ -1,11 +1,12 @@
 use async_trait::async_trait;
-use serde::Deserialize;
-use std::io::Write;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::io::{self, Write};
 use std::time::Duration;
 use thiserror::Error;
 use ureq;
 
-use crate::component::Component;
+use crate::component::{BoxedCollected, Collected, CollectedError, Component, ComponentError};
 use crate::config::global_config::GlobalConfig;
 use crate::default_prepare;
 
@@ -27,7 +28,7 @@ pub struct Weather {
 
     #[knus(property, default = 5)]
     #[serde(default = "default_timeout")]
-    timeout: u64,
+    pub timeout: u64,
 }
 
 fn default_timeout() -> u64 {
@@ -36,10 +37,14 @@ fn default_timeout() -> u64 {
 
 #[async_trait]
 impl Component for Weather {
-    async fn print(self: Box<Self>, _global_config: &GlobalConfig, _width: Option<usize>) {
-        self.print_or_error()
-            .unwrap_or_else(|err| println!("Weather error: {err}"));
-        println!();
+    async fn collect(self: Box<Self>, _global_config: &GlobalConfig) -> BoxedCollected {
+        match self.collect_or_error() {
+            Ok(collected) => Box::new(collected),
+            Err(err) => Box::new(CollectedError(ComponentError::new("weather", err))),
+        }
+    }
+    fn name(&self) -> &'static str {
+        "weather"
     }
     default_prepare!();
 }
@@ -67,8 +72,31 @@ pub enum WeatherError {
     IO(#[from] std::io::Error),
 }
 
+#[derive(Serialize)]
+struct CollectedWeather {
+    first_line: String,
+    body: String,
+}
+
+impl Collected for CollectedWeather {
+    fn print(
+        self: Box<Self>,
+        _global_config: &GlobalConfig,
+        _width: Option<usize>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        out.write_all(&[self.first_line.as_bytes(), "\n".as_bytes()].concat())?;
+        out.write_all(self.body.as_bytes())?;
+        writeln!(out)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!(self)
+    }
+}
+
 impl Weather {
-    pub fn print_or_error(self) -> Result<(), WeatherError> {
+    fn collect_or_error(self) -> Result<CollectedWeather, WeatherError> {
         let url = match self.url {
             Some(url) => url,
             None => {
@@ -118,11 +146,6 @@ impl Weather {
             .collect::<Vec<String>>()
             .join("");
 
-        let mut out = std::io::stdout();
-        out.write_all(&[first_line.as_bytes(), "\n".as_bytes()].concat())?;
-        out.write_all(body.as_bytes())?;
-        out.flush()?;
-
-        Ok(())
+        Ok(CollectedWeather { first_line, body })
     }
 }
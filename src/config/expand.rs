@@ -0,0 +1,260 @@
+use knus::ast::{Literal, SpannedNode};
+use knus::span::Span;
+use std::env;
+
+use crate::config::when::{current_user, hostname};
+
+/// The names of the facts, written as `{name}`
+const FACTS: [&str; 4] = ["hostname", "user", "home", "uid"];
+
+/// The nodes whose arguments are expanded: commands and paths
+/// (the `command` component, `include`, `plugin`, the `file` condition of `when` and `cache-dir`)
+pub const EXPANDED_ARGUMENTS: [&str; 5] = ["command", "include", "plugin", "file", "cache-dir"];
+
+/// The properties whose values are expanded: paths, sockets, URLs and titles
+const EXPANDED_PROPERTIES: [&str; 9] = [
+    "path",
+    "url",
+    "state-file",
+    "socket",
+    "mount-point",
+    "title",
+    "display-name",
+    "name",
+    "prefix",
+];
+
+/// Facts about the host and the user running `rust-motd`, which can be used in the configuration
+/// A fact that cannot be found is left as is
+pub struct Facts {
+    hostname: Option<String>,
+    user: Option<String>,
+    home: Option<String>,
+    uid: String,
+}
+
+impl Facts {
+    pub fn current() -> Self {
+        Facts {
+            hostname: hostname(),
+            user: current_user(),
+            home: env::var("HOME").ok(),
+            uid: unsafe { libc::getuid() }.to_string(),
+        }
+    }
+
+    /// The value of a fact written as `{name}`
+    fn fact(&self, name: &str) -> Option<&str> {
+        match name {
+            "hostname" => self.hostname.as_deref(),
+            "user" => self.user.as_deref(),
+            "home" => self.home.as_deref(),
+            "uid" => Some(&self.uid),
+            _ => None,
+        }
+    }
+
+    /// The value of an environment variable written as `${NAME}`
+    /// Variables that shells set without exporting them (like `UID`) fall back to the facts
+    fn variable(&self, name: &str) -> Option<String> {
+        env::var(name).ok().or_else(|| match name {
+            "HOSTNAME" => self.hostname.clone(),
+            "USER" => self.user.clone(),
+            "UID" => Some(self.uid.clone()),
+            _ => None,
+        })
+    }
+
+    /// Expand a leading `~`, `${NAME}` variables and `{name}` facts
+    /// A leading `~~`, `$${NAME}` and `{{name}}` are left as `~`, `${NAME}` and `{name}` instead,
+    /// for shell commands that use the variable themselves or print braces
+    /// Anything else is left as is, like `{one}` in the format of `load-avg`,
+    /// or variables that are not set (which a shell command may set itself)
+    pub fn expand(&self, value: &str) -> String {
+        let mut result = String::new();
+        let mut rest = value;
+        if let Some(escaped) = rest.strip_prefix('~').filter(|rest| rest.starts_with('~')) {
+            rest = escaped;
+        } else if let Some(home) = &self.home {
+            if rest == "~" || rest.starts_with("~/") {
+                result += home;
+                rest = &rest[1..];
+            }
+        }
+        while let Some(end) = rest.find('}') {
+            let Some(start) = rest[..end].rfind('{') else {
+                result += &rest[..=end];
+                rest = &rest[end + 1..];
+                continue;
+            };
+            let name = &rest[start + 1..end];
+            let escaped_variable = is_variable_name(name) && rest[..start].ends_with("$$");
+            let escaped_fact = FACTS.contains(&name)
+                && rest[..start].ends_with('{')
+                && rest[end + 1..].starts_with('}');
+            if escaped_variable || escaped_fact {
+                result += &rest[..start - 1];
+                result += &rest[start..=end];
+                rest = &rest[end + 1 + usize::from(escaped_fact)..];
+                continue;
+            }
+            let (prefix, value) = match rest[..start].strip_suffix('$') {
+                Some(prefix) if is_variable_name(name) => (prefix, self.variable(name)),
+                _ => (&rest[..start], self.fact(name).map(str::to_string)),
+            };
+            match value {
+                Some(value) => {
+                    result += prefix;
+                    result += &value;
+                }
+                None => result += &rest[..=end],
+            }
+            rest = &rest[end + 1..];
+        }
+        result + rest
+    }
+
+    /// Escape a value so that `Facts::expand` gives it back as is
+    pub fn escape(value: &str) -> String {
+        let mut result = String::new();
+        if value == "~" || value.starts_with("~/") || value.starts_with("~~") {
+            result += "~";
+        }
+        let mut rest = value;
+        while let Some(end) = rest.find('}') {
+            let Some(start) = rest[..end].rfind('{') else {
+                result += &rest[..=end];
+                rest = &rest[end + 1..];
+                continue;
+            };
+            let name = &rest[start + 1..end];
+            result += &rest[..start];
+            if is_variable_name(name) && rest[..start].ends_with('$') {
+                result += "$";
+                result += &rest[start..=end];
+            } else if FACTS.contains(&name) {
+                result += &format!("{{{}}}", &rest[start..=end]);
+            } else {
+                result += &rest[start..=end];
+            }
+            rest = &rest[end + 1..];
+        }
+        result + rest
+    }
+
+    /// Expand the strings of the arguments and properties of the nodes and their children
+    /// Only paths, commands and titles are expanded (see `is_expanded`),
+    /// not settings like the characters of the progress bars
+    pub fn expand_nodes(&self, nodes: &mut [SpannedNode<Span>]) {
+        for node in nodes {
+            let node = &mut **node;
+            let arguments = node
+                .arguments
+                .iter_mut()
+                .filter(|_| EXPANDED_ARGUMENTS.contains(&&**node.node_name));
+            let properties = node
+                .properties
+                .iter_mut()
+                .filter(|(name, _)| is_expanded(name))
+                .map(|(_, value)| value);
+            for value in arguments.chain(properties) {
+                if let Literal::String(string) = &*value.literal {
+                    let expanded = self.expand(string);
+                    if expanded != **string {
+                        *value.literal = Literal::String(expanded.into());
+                    }
+                }
+            }
+            if let Some(children) = &mut node.children {
+                self.expand_nodes(children);
+            }
+        }
+    }
+}
+
+/// Whether the value of a property (or setting) with this name is expanded
+pub fn is_expanded(name: &str) -> bool {
+    EXPANDED_PROPERTIES.contains(&name)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let facts = Facts {
+            hostname: Some("web-01".to_string()),
+            user: Some("alice".to_string()),
+            home: Some("/home/alice".to_string()),
+            uid: "1000".to_string(),
+        };
+        assert_eq!(
+            facts.expand("~/certs/{hostname}.pem"),
+            "/home/alice/certs/web-01.pem"
+        );
+        assert_eq!(
+            facts.expand("unix:///run/user/${UID}/podman/podman.sock"),
+            "unix:///run/user/1000/podman/podman.sock"
+        );
+        assert_eq!(facts.expand("{user}@{hostname}"), "alice@web-01");
+        assert_eq!(
+            facts.expand("Load: {one:.2} {five}"),
+            "Load: {one:.2} {five}"
+        );
+        assert_eq!(
+            facts.expand("${RUST_MOTD_UNSET_VARIABLE} ${1} $ {uid}"),
+            "${RUST_MOTD_UNSET_VARIABLE} ${1} $ 1000"
+        );
+        assert_eq!(facts.expand("~user {home"), "~user {home");
+        assert_eq!(facts.expand("} { {uid}"), "} { 1000");
+    }
+
+    #[test]
+    fn test_expand_escapes() {
+        let facts = Facts {
+            hostname: Some("web-01".to_string()),
+            user: Some("alice".to_string()),
+            home: Some("/home/alice".to_string()),
+            uid: "1000".to_string(),
+        };
+        assert_eq!(
+            facts.expand(r#"for u in $(users); do echo "$${u} on {hostname}: {{user}}"; done"#),
+            r#"for u in $(users); do echo "${u} on web-01: {user}"; done"#
+        );
+        assert_eq!(facts.expand("$${HOME} $$ {home}"), "${HOME} $$ /home/alice");
+        assert_eq!(facts.expand("{{one}} {{{uid}}}"), "{{one}} {{uid}}");
+        assert_eq!(facts.expand("~~/.ssh ~/"), "~/.ssh ~/");
+    }
+
+    #[test]
+    fn test_escape() {
+        let facts = Facts {
+            hostname: Some("web-01".to_string()),
+            user: Some("alice".to_string()),
+            home: Some("/home/alice".to_string()),
+            uid: "1000".to_string(),
+        };
+        for value in [
+            "~",
+            "~/.ssh",
+            "~~",
+            "~user",
+            "$",
+            "${HOME} $${HOME} ${1} $ {uid}",
+            "{hostname} {{user}} {{{home}}} {word} {uid}}{user}",
+            "} { {user",
+        ] {
+            assert_eq!(facts.expand(&Facts::escape(value)), value);
+        }
+        assert_eq!(Facts::escape("~/{user}/${HOME}"), "~~/{{user}}/$${HOME}");
+    }
+}
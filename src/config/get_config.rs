@@ -3,10 +3,15 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 
-use crate::config::kdl_config::{parse_kdl, KdlConfigError};
+use crate::config::global_config::UserConfigPolicy;
+use crate::config::kdl_config::{
+    into_config, parse_kdl, parse_layered_kdl, read_user_config_policy, KdlConfigError, Layer,
+};
+use crate::config::registry::Registry;
 use crate::config::toml_config::parse_toml;
 use crate::config::toml_config::TomlConfigError;
 use crate::config::Config;
+use crate::sysroot::SysRoot;
 
 #[derive(Error, Debug, miette::Diagnostic)]
 pub enum ConfigError {
@@ -20,6 +25,12 @@ pub enum ConfigError {
     #[error("The configuration file must be .kdl or .toml. Found {0}.")]
     ConfigFormatError(String),
 
+    #[error(
+        "The system configuration has `user-config \"layer\"`, \
+        so the configuration of the user must be .kdl. Found {0}."
+    )]
+    LayerFormatError(PathBuf),
+
     #[error(transparent)]
     ConfigHomeError(#[from] std::env::VarError),
 
@@ -37,37 +48,104 @@ pub enum ConfigError {
     TomlError(#[from] TomlConfigError),
 }
 
-fn get_config_path(config_path: Option<String>) -> Result<PathBuf, ConfigError> {
-    if let Some(file_path) = config_path {
-        return Ok(PathBuf::from(file_path));
-    }
+/// The configuration for all users, used when a user does not have a configuration of their own
+const SYSTEM_CONFIG_PATH: &str = "/etc/rust-motd/config.kdl";
+
+/// Fragments in this directory are read after the system configuration, in lexical order
+const CONF_D_DIR: &str = "/etc/rust-motd/conf.d";
 
+/// The configuration of the user running `rust-motd`, if it exists
+fn get_user_config_path() -> Result<Option<PathBuf>, ConfigError> {
     let config_base = env::var("XDG_CONFIG_HOME").unwrap_or(env::var("HOME")? + "/.config");
 
     for basename in ["rust-motd/config.kdl", "rust-motd/config.toml"] {
         let config_base = Path::new(&config_base).join(Path::new(basename));
         if config_base.exists() {
-            return Ok(config_base);
+            return Ok(Some(config_base));
         }
     }
 
-    Err(ConfigError::ConfigNotFound)
+    Ok(None)
 }
 
-pub fn get_config(config_path: Option<String>) -> Result<Config, ConfigError> {
-    let config_path = get_config_path(config_path)?;
-    let config_str = fs::read_to_string(&config_path)?;
+/// Parse a configuration file, with the fragments in `conf_d` if it is a KDL file
+fn parse_config(
+    config_path: &Path,
+    registry: &Registry,
+    conf_d: Option<&Path>,
+) -> Result<Config, ConfigError> {
+    let config_str = fs::read_to_string(config_path)?;
+
+    match extension(config_path) {
+        "toml" => Ok(parse_toml(&config_str)?),
+        "kdl" => Ok(parse_kdl(config_path, &config_str, registry, conf_d)?),
+        other => Err(ConfigError::ConfigFormatError(other.to_string())),
+    }
+}
 
+fn extension(config_path: &Path) -> &str {
     let extension = config_path
         .extension()
         .expect("Could not determine extension for config file.");
-    let extension = extension
+    extension
         .to_str()
-        .expect("Could not determine extension for config file.");
+        .expect("Could not determine extension for config file.")
+}
 
-    match extension {
-        "toml" => Ok(parse_toml(&config_str)?),
-        "kdl" => Ok(parse_kdl(&config_path, &config_str)?),
-        other => Err(ConfigError::ConfigFormatError(other.to_string())),
+/// Read the configuration given on the command line
+/// Otherwise, read the configuration of the user and the system configuration,
+/// and combine them according to the `user-config` setting of the system configuration
+/// The fragments in `conf.d` are only added to the system configuration
+/// The system configuration is read under `sysroot`, like the root of the host in a container
+/// The components of the registry can be used in KDL configurations
+pub fn get_config(
+    config_path: Option<String>,
+    registry: &Registry,
+    sysroot: &SysRoot,
+) -> Result<Config, ConfigError> {
+    if let Some(file_path) = config_path {
+        return parse_config(Path::new(&file_path), registry, None);
+    }
+
+    let user_path = get_user_config_path();
+    let system_path = sysroot.path(SYSTEM_CONFIG_PATH);
+    let conf_d = sysroot.path(CONF_D_DIR);
+    if !system_path.exists() {
+        return parse_config(
+            &user_path?.ok_or(ConfigError::ConfigNotFound)?,
+            registry,
+            None,
+        );
+    }
+    // The system configuration is only parsed once, since it may be layered below the user's
+    let system_str = fs::read_to_string(&system_path)?;
+    let system = Layer::read(&system_path, &system_str, registry, Some(&conf_d))
+        .and_then(|layer| Ok((layer.user_config()?, layer)));
+    // Without a home directory, there is no configuration of the user
+    let Ok(Some(user_path)) = user_path else {
+        return Ok(into_config(system?.1)?);
+    };
+    let (policy, system) = match system {
+        Ok(system) => system,
+        // An invalid system configuration does not matter to the users who replace it,
+        // as long as its `user-config` setting can be read
+        Err(err) => {
+            return match read_user_config_policy(&system_path, &system_str) {
+                Some(UserConfigPolicy::Replace) => parse_config(&user_path, registry, None),
+                _ => Err(err.into()),
+            };
+        }
+    };
+
+    match policy {
+        UserConfigPolicy::Forbid => Ok(into_config(system)?),
+        UserConfigPolicy::Replace => parse_config(&user_path, registry, None),
+        UserConfigPolicy::Layer => {
+            if extension(&user_path) != "kdl" {
+                return Err(ConfigError::LayerFormatError(user_path));
+            }
+            let user_str = fs::read_to_string(&user_path)?;
+            Ok(parse_layered_kdl(system, &user_path, &user_str, registry)?)
+        }
     }
 }
@@ -1,5 +1,9 @@
 use knus;
 use serde::Deserialize;
+use std::env;
+
+use crate::config::theme::Theme;
+use crate::sysroot::SysRoot;
 
 #[derive(Debug, Deserialize, knus::Decode)]
 pub struct GlobalConfig {
@@ -24,14 +28,198 @@ pub struct GlobalConfig {
 
     #[knus(child, unwrap(argument), default=default_progress_width())]
     #[serde(default = "default_progress_width")]
-    pub progress_width: usize,
+    pub progress_width: ProgressWidth,
+
+    /// Use eighth blocks to draw the partially filled part of the bars
+    #[knus(child, unwrap(argument), default)]
+    #[serde(default)]
+    pub progress_smooth: bool,
+
+    /// Colour each part of the bars according to its own position instead of the whole ratio
+    #[knus(child, unwrap(argument), default)]
+    #[serde(default)]
+    pub progress_gradient: bool,
+
+    #[knus(child, unwrap(argument), default)]
+    #[serde(default)]
+    pub progress_label: ProgressLabel,
 
     #[knus(child, unwrap(argument), default=default_time_format())]
     #[serde(default = "default_time_format")]
     pub time_format: String,
 
+    #[knus(child, unwrap(argument), default)]
+    #[serde(default)]
+    pub color: ColorMode,
+
+    /// Colours used by all components
+    #[knus(child, default)]
+    #[serde(default)]
+    pub theme: Theme,
+
+    /// Default maximum time in seconds a component may take to prepare and collect its data
+    #[knus(child, unwrap(argument))]
+    #[serde(default)]
+    pub timeout: Option<u64>,
+
+    /// Where to store the output of components with a `cache-ttl`
+    #[knus(child, unwrap(argument))]
+    #[serde(default)]
+    pub cache_dir: Option<String>,
+
+    /// What to do with the configuration of a user, only read from the system configuration
+    #[knus(child, unwrap(argument), default)]
+    #[serde(default)]
+    pub user_config: UserConfigPolicy,
+
+    /// How to print the errors of the components
+    #[knus(child, unwrap(argument), default)]
+    #[serde(default)]
+    pub on_error: ErrorPolicy,
+
     #[serde(default = "default_show_legacy_warning")]
     pub show_legacy_warning: bool,
+
+    /// Where the components read the state of the system from, set with `--sysroot`
+    #[serde(skip)]
+    pub sysroot: SysRoot,
+
+    /// The merged `global` nodes as KDL, which identifies these settings in the cache
+    #[serde(skip)]
+    pub kdl: String,
+}
+
+/// Whether to print colours (and other styles)
+#[derive(knus::DecodeScalar, Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorMode {
+    Always,
+    Never,
+    /// Print colours only if the output is a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+}
+
+impl ColorMode {
+    /// Whether to print colours to an output that is or is not a terminal
+    /// `NO_COLOR` (https://no-color.org/) only changes the default, `always` in the config wins
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => {
+                is_terminal && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+        }
+    }
+}
+
+/// What to do when both the system configuration (`/etc/rust-motd/config.kdl`)
+/// and the configuration of the user exist
+#[derive(knus::DecodeScalar, Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UserConfigPolicy {
+    /// Use the configuration of the user instead of the system configuration
+    #[default]
+    Replace,
+    /// Apply the configuration of the user on top of the system configuration
+    Layer,
+    /// Ignore the configuration of the user
+    Forbid,
+}
+
+/// How to print the errors of the components (like a certificate that cannot be read)
+#[derive(knus::DecodeScalar, Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorPolicy {
+    /// In place of the component, with its heading
+    #[default]
+    Inline,
+    /// In place of the component, on a single line
+    Compact,
+    /// Not at all (they are still printed to stderr with `--verbose`)
+    Hide,
+    /// Together, after all the components
+    Footer,
+}
+
+/// Where to print the percentage of the progress bars
+#[derive(knus::DecodeScalar, Debug, Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgressLabel {
+    #[default]
+    None,
+    /// In the middle of the bar
+    Inside,
+    /// After the bar
+    Beside,
+}
+
+/// Width of the progress bars (and of the components that align with them)
+/// Either a number of columns or `"auto"`, to fill the width of the terminal
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(try_from = "ProgressWidthValue")]
+pub enum ProgressWidth {
+    Fixed(usize),
+    Auto,
+}
+
+impl ProgressWidth {
+    /// The width to use when the output is not a terminal (or its size is unknown)
+    pub fn fallback(self) -> usize {
+        match self {
+            ProgressWidth::Fixed(width) => width,
+            ProgressWidth::Auto => 80,
+        }
+    }
+}
+
+impl<S: knus::traits::ErrorSpan> knus::DecodeScalar<S> for ProgressWidth {
+    fn type_check(
+        type_name: &Option<knus::span::Spanned<knus::ast::TypeName, S>>,
+        ctx: &mut knus::decode::Context<S>,
+    ) {
+        <usize as knus::DecodeScalar<S>>::type_check(type_name, ctx);
+    }
+
+    fn raw_decode(
+        value: &knus::span::Spanned<knus::ast::Literal, S>,
+        ctx: &mut knus::decode::Context<S>,
+    ) -> Result<Self, knus::errors::DecodeError<S>> {
+        match &**value {
+            knus::ast::Literal::String(keyword) if &**keyword == "auto" => Ok(ProgressWidth::Auto),
+            knus::ast::Literal::Int(_) => {
+                <usize as knus::DecodeScalar<S>>::raw_decode(value, ctx).map(ProgressWidth::Fixed)
+            }
+            _ => Err(knus::errors::DecodeError::unexpected(
+                value,
+                "value",
+                "expected a number of columns or \"auto\"",
+            )),
+        }
+    }
+}
+
+/// How `progress_width` is written in the TOML configuration
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProgressWidthValue {
+    Fixed(usize),
+    Keyword(String),
+}
+
+impl TryFrom<ProgressWidthValue> for ProgressWidth {
+    type Error = String;
+
+    fn try_from(value: ProgressWidthValue) -> Result<Self, Self::Error> {
+        match value {
+            ProgressWidthValue::Fixed(width) => Ok(ProgressWidth::Fixed(width)),
+            ProgressWidthValue::Keyword(keyword) if keyword == "auto" => Ok(ProgressWidth::Auto),
+            ProgressWidthValue::Keyword(keyword) => Err(format!(
+                "expected a number of columns or \"auto\", found \"{keyword}\""
+            )),
+        }
+    }
 }
 
 fn default_progress_character() -> String {
@@ -46,8 +234,8 @@ fn default_progress_suffix() -> String {
     "]".to_string()
 }
 
-fn default_progress_width() -> usize {
-    80
+fn default_progress_width() -> ProgressWidth {
+    ProgressWidth::Fixed(80)
 }
 
 fn default_time_format() -> String {
@@ -68,8 +256,19 @@ impl Default for GlobalConfig {
             progress_prefix: default_progress_prefix(),
             progress_suffix: default_progress_suffix(),
             progress_width: default_progress_width(),
+            progress_smooth: false,
+            progress_gradient: false,
+            progress_label: ProgressLabel::default(),
             time_format: default_time_format(),
+            color: ColorMode::default(),
+            theme: Theme::default(),
+            timeout: None,
+            cache_dir: None,
+            user_config: UserConfigPolicy::default(),
+            on_error: ErrorPolicy::default(),
             show_legacy_warning: default_show_legacy_warning(),
+            sysroot: SysRoot::default(),
+            kdl: String::new(),
         }
     }
 }
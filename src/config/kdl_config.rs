@@ -1,7 +1,17 @@
-use std::path::Path;
+use itertools::Itertools;
+use knus::ast::{Decimal, Integer, Literal, Node, Radix, SpannedNode, Value};
+use knus::decode::Context;
+use knus::errors::DecodeError;
+use knus::span::{Span, Spanned};
+use knus::DecodeChildren;
+use miette::{LabeledSpan, NamedSource};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use thiserror::Error;
 
-use crate::component::BoxedComponent;
+use crate::component::{BoxedComponent, ComponentOptions, ConfiguredComponent};
 use crate::components::cg_stats::CgStats;
 use crate::components::command::Command;
 use crate::components::docker::Docker;
@@ -12,16 +22,23 @@ use crate::components::last_login::LastLogin;
 use crate::components::last_run::LastRun;
 use crate::components::loadavg::LoadAvg;
 use crate::components::memory::Memory;
+use crate::components::plugin::Plugin;
 use crate::components::service_status::{ServiceStatus, UserServiceStatus};
 use crate::components::ssl_certs::SSLCerts;
+use crate::components::summary::Summary;
 use crate::components::uptime::Uptime;
 use crate::components::weather::Weather;
-use crate::config::global_config::GlobalConfig;
+use crate::config::expand::Facts;
+use crate::config::global_config::{GlobalConfig, UserConfigPolicy};
+use crate::config::registry::Registry;
+use crate::config::when::{glob_match, When};
 use crate::config::Config;
+use crate::layout::{Placement, RowLayout};
 
 const EXPECTED_VERSION: &str = "1.0";
 
 #[derive(knus::Decode, Debug)]
+#[knus(span_type = Span)]
 pub enum ComponentNode {
     Command(Command),
     CgStats(CgStats),
@@ -33,19 +50,376 @@ pub enum ComponentNode {
     LastRun(LastRun),
     LoadAvg(LoadAvg),
     Memory(Memory),
+    Plugin(Plugin),
     SSLCerts(SSLCerts),
     ServiceStatus(ServiceStatus),
+    Summary(Summary),
     Uptime(Uptime),
     UserServiceStatus(UserServiceStatus),
     Weather(Weather),
+    Row(Row),
+    System(SystemComponents),
+    Hide(HideComponents),
+    /// A component of the `Registry`
+    #[knus(skip)]
+    Custom(BoxedComponent),
 }
 
+/// In the configuration of a user layered on the system configuration,
+/// the place of the components of the system configuration
+/// Either all of them (that are not placed elsewhere), or the ones with the given names
 #[derive(knus::Decode, Debug)]
-pub struct KdlConfig {
+pub struct SystemComponents {
+    #[knus(arguments)]
+    names: Vec<String>,
+}
+
+/// In the configuration of a user layered on the system configuration,
+/// hide the components of the system configuration with the given names
+#[derive(knus::Decode, Debug)]
+pub struct HideComponents {
+    #[knus(arguments)]
+    names: Vec<String>,
+}
+
+/// Prints its columns side by side
+#[derive(knus::Decode, Debug)]
+#[knus(span_type = Span)]
+pub struct Row {
+    /// Number of spaces between the columns
+    #[knus(property, default = 2)]
+    gap: usize,
+    #[knus(children(name = "column"))]
+    columns: Vec<Column>,
+}
+
+/// A column of a `Row`, containing components printed one below the other
+#[derive(knus::Decode, Debug)]
+#[knus(span_type = Span)]
+pub struct Column {
+    /// The width of the column, by default the columns share the width of the terminal
+    #[knus(property)]
+    width: Option<usize>,
+    #[knus(children)]
+    components: Vec<ComponentEntry>,
+}
+
+/// A component node along with the options that every component accepts (like `timeout`)
+/// The generic options are removed from the node before it is decoded as a `ComponentNode`,
+/// so that the components do not each have to declare them
+#[derive(Debug)]
+pub struct ComponentEntry {
+    /// The name of the node, like `filesystems`
+    pub name: String,
+    pub node: ComponentNode,
+    pub options: ComponentOptions,
+    /// The node without the generic options, see `to_kdl`
+    pub kdl: String,
+}
+
+impl knus::Decode<Span> for ComponentEntry {
+    fn decode_node(
+        node: &SpannedNode<Span>,
+        ctx: &mut Context<Span>,
+    ) -> Result<Self, DecodeError<Span>> {
+        let mut node = node.clone();
+        let id = node.properties.remove("id");
+        // The other options of a row apply to all of its components, but an `id` must be unique
+        if let Some(id) = id.as_ref().filter(|_| &**node.node_name == "row") {
+            return Err(DecodeError::unexpected(
+                &id.literal,
+                "property",
+                "a row cannot have an `id`, give one to its components instead",
+            ));
+        }
+        let id = id
+            .map(|value| knus::traits::DecodeScalar::decode(&value, ctx))
+            .transpose()?;
+        let mut option = |name: &str| {
+            node.properties
+                .remove(name)
+                .map(|value| knus::traits::DecodeScalar::decode(&value, ctx))
+                .transpose()
+        };
+        let timeout = option("timeout")?;
+        let cache_ttl = option("cache-ttl")?;
+        let mut when = vec![];
+        if let Some(children) = node.children.as_mut() {
+            for child in children.extract_if(.., |child| &**child.node_name == "when") {
+                when.push(When::decode_node(&child, ctx)?);
+            }
+        }
+        let options = ComponentOptions {
+            id,
+            timeout,
+            cache_ttl,
+            when,
+        };
+
+        let kdl = to_kdl(&node);
+        let decoder = ctx
+            .get::<Registry>()
+            .and_then(|registry| registry.decoder(&node.node_name));
+        let component = match decoder {
+            Some(decode) => ComponentNode::Custom(decode(&node, ctx)?),
+            None => ComponentNode::decode_node(&node, ctx)?,
+        };
+
+        Ok(ComponentEntry {
+            name: node.node_name.to_string(),
+            node: component,
+            options,
+            kdl,
+        })
+    }
+}
+
+impl ComponentEntry {
+    /// Whether the node has this name (like `docker`) or this `id`
+    fn is_named(&self, name: &str) -> bool {
+        self.name == name || self.options.id.as_deref() == Some(name)
+    }
+}
+
+/// A configuration file: the main one, an included one, or a fragment in `conf.d`
+/// Every file may set global settings, add components and include other files
+#[derive(knus::Decode, Debug)]
+#[knus(span_type = Span)]
+pub struct KdlFile {
+    #[knus(children(name = "include"))]
+    includes: Vec<Include>,
     #[knus(child)]
-    pub global: GlobalConfig,
-    #[knus(child, unwrap(children))]
-    pub components: Vec<ComponentNode>,
+    global: Option<GlobalNode>,
+    #[knus(child, unwrap(children), default)]
+    components: Vec<ComponentEntry>,
+}
+
+/// A configuration file whose strings have been expanded
+/// (`~`, `${NAME}` variables and `{name}` facts, see `Facts::expand`)
+/// The strings are expanded before they are decoded, so that errors still point into the file
+struct ExpandedFile(KdlFile);
+
+impl DecodeChildren<Span> for ExpandedFile {
+    fn decode_children(
+        nodes: &[SpannedNode<Span>],
+        ctx: &mut Context<Span>,
+    ) -> Result<Self, DecodeError<Span>> {
+        let mut nodes = nodes.to_vec();
+        Facts::current().expand_nodes(&mut nodes);
+        KdlFile::decode_children(&nodes, ctx).map(ExpandedFile)
+    }
+}
+
+/// Include other configuration files, relative to the directory of the including file
+/// The file name may contain `*` and `?` wildcards, matching files are included in lexical order
+#[derive(knus::Decode, Debug)]
+pub struct Include {
+    #[knus(argument)]
+    path: String,
+}
+
+/// A node written back as KDL, without its comments, formatting and type annotations of strings
+/// Nodes that are configured the same way give the same text, which is used to identify
+/// the output of a component in the cache (unlike its `Debug` representation,
+/// this only depends on the configuration)
+fn to_kdl(node: &Node<Span>) -> String {
+    let mut kdl = format!("{:?}", &**node.node_name);
+    for value in &node.arguments {
+        kdl += &format!(" {}", value_to_kdl(value));
+    }
+    for (name, value) in &node.properties {
+        kdl += &format!(" {:?}={}", &***name, value_to_kdl(value));
+    }
+    // An empty block is the same as no block, like once the `when` nodes are removed
+    if let Some(children) = node
+        .children
+        .as_ref()
+        .filter(|children| !children.is_empty())
+    {
+        kdl += " {";
+        for child in children.iter() {
+            kdl += &format!(" {};", to_kdl(child));
+        }
+        kdl += " }";
+    }
+    kdl
+}
+
+fn value_to_kdl(value: &Value<Span>) -> String {
+    let literal = match &*value.literal {
+        Literal::Null => "null".to_string(),
+        Literal::Bool(value) => value.to_string(),
+        Literal::Int(Integer(radix, digits)) => {
+            let prefix = match radix {
+                Radix::Bin => "0b",
+                Radix::Oct => "0o",
+                Radix::Dec => "",
+                Radix::Hex => "0x",
+            };
+            format!("{prefix}{digits}")
+        }
+        Literal::Decimal(Decimal(digits)) => digits.to_string(),
+        Literal::String(string) => format!("{string:?}"),
+    };
+    match &value.type_name {
+        Some(type_name) => format!("({}){literal}", **type_name),
+        None => literal,
+    }
+}
+
+/// The `global` node of a file
+/// It is checked when the file is read, so that errors point at the right file,
+/// but it is only decoded once the `global` nodes of all files are merged
+#[derive(Debug)]
+pub struct GlobalNode {
+    node: SpannedNode<Span>,
+    /// The files the settings come from, with the offset added to their spans
+    /// The spans of each merged file are moved after the ones of the previous files,
+    /// so that an error in the merged node can be traced back to its file
+    sources: Vec<(usize, NamedSource<Arc<String>>)>,
+}
+
+impl knus::Decode<Span> for GlobalNode {
+    fn decode_node(
+        node: &SpannedNode<Span>,
+        ctx: &mut Context<Span>,
+    ) -> Result<Self, knus::errors::DecodeError<Span>> {
+        GlobalConfig::decode_node(node, ctx)?;
+        Ok(GlobalNode {
+            node: node.clone(),
+            sources: vec![],
+        })
+    }
+}
+
+impl GlobalNode {
+    /// The end of the spans of the merged files
+    fn end(&self) -> usize {
+        self.sources
+            .last()
+            .map_or(0, |(offset, source)| offset + source.inner().len())
+    }
+
+    /// Merge the settings of a later file: each setting it sets replaces the one of this node
+    /// Settings with children (like `theme`) are replaced as a whole
+    fn merge(&mut self, mut other: GlobalNode) {
+        let offset = self.end();
+        self.sources.extend(
+            other
+                .sources
+                .drain(..)
+                .map(|(start, source)| (start + offset, source)),
+        );
+        let Some(other_children) = other.node.children.take() else {
+            return;
+        };
+        let mut other_children =
+            shift(other_children, offset).map(|children| -> Vec<SpannedNode<Span>> {
+                children
+                    .into_iter()
+                    .map(|child| shift_node(child, offset))
+                    .collect()
+            });
+        match &mut self.node.children {
+            Some(children) => {
+                for child in other_children.drain(..) {
+                    children.retain(|existing| existing.node_name != child.node_name);
+                    children.push(child);
+                }
+            }
+            None => self.node.children = Some(other_children),
+        }
+    }
+
+    /// Decode the merged settings, the errors point at the file the faulty setting comes from
+    fn decode(&self) -> Result<GlobalConfig, KdlConfigError> {
+        knus::decode::node(&self.node).map_err(|errors| KdlConfigError::GlobalError {
+            errors: errors.into_iter().map(|error| self.locate(error)).collect(),
+        })
+    }
+
+    fn locate(&self, error: DecodeError<Span>) -> GlobalSettingError {
+        let message = error.to_string();
+        let labels: Vec<_> = miette::Diagnostic::labels(&error)
+            .into_iter()
+            .flatten()
+            .collect();
+        let position = labels.first().map_or(0, LabeledSpan::offset);
+        let Some((offset, source)) = self
+            .sources
+            .iter()
+            .rev()
+            .find(|(offset, _)| *offset <= position)
+        else {
+            return GlobalSettingError {
+                message,
+                source_code: None,
+                labels: vec![],
+            };
+        };
+        GlobalSettingError {
+            message,
+            source_code: Some(source.clone()),
+            labels: labels
+                .into_iter()
+                .map(|label| {
+                    LabeledSpan::new(
+                        label.label().map(ToString::to_string),
+                        label.offset() - offset,
+                        label.len(),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+fn shift<T>(spanned: Spanned<T, Span>, offset: usize) -> Spanned<T, Span> {
+    spanned.map_span(|Span(start, end)| Span(start + offset, end + offset))
+}
+
+fn shift_value(value: Value<Span>, offset: usize) -> Value<Span> {
+    Value {
+        type_name: value.type_name.map(|type_name| shift(type_name, offset)),
+        literal: shift(value.literal, offset),
+    }
+}
+
+/// Move all the spans of `node` and of its children by `offset`
+fn shift_node(node: SpannedNode<Span>, offset: usize) -> SpannedNode<Span> {
+    shift(node, offset).map(|node| Node {
+        type_name: node.type_name.map(|type_name| shift(type_name, offset)),
+        node_name: shift(node.node_name, offset),
+        arguments: node
+            .arguments
+            .into_iter()
+            .map(|value| shift_value(value, offset))
+            .collect(),
+        properties: node
+            .properties
+            .into_iter()
+            .map(|(name, value)| (shift(name, offset), shift_value(value, offset)))
+            .collect(),
+        children: node.children.map(|children| {
+            shift(children, offset).map(|children| {
+                children
+                    .into_iter()
+                    .map(|child| shift_node(child, offset))
+                    .collect()
+            })
+        }),
+    })
+}
+
+/// An invalid setting in the merged global settings, pointing into the file it comes from
+#[derive(Error, Debug, miette::Diagnostic)]
+#[error("{message}")]
+pub struct GlobalSettingError {
+    message: String,
+    #[source_code]
+    source_code: Option<NamedSource<Arc<String>>>,
+    #[label(collection)]
+    labels: Vec<LabeledSpan>,
 }
 
 #[derive(Error, Debug, miette::Diagnostic)]
@@ -59,13 +433,299 @@ pub enum KdlConfigError {
 
     #[error("The only supported config version is {0} but your config has version {1}.")]
     VersionMismatch(&'static str, String),
+
+    #[error("A `row` cannot contain another `row`.")]
+    NestedRow,
+
+    #[error("Could not read {path}")]
+    ReadError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("{0} is included more than once.")]
+    IncludedTwice(PathBuf),
+
+    #[error("More than one component has the id `{0}`.")]
+    DuplicateId(String),
+
+    #[error("Invalid global settings.")]
+    GlobalError {
+        #[related]
+        errors: Vec<GlobalSettingError>,
+    },
+
+    #[error(
+        "`{0}` can only be used in the configuration of a user, \
+        with `user-config \"layer\"` in the system configuration."
+    )]
+    LayerOnly(&'static str),
 }
 
-pub fn parse_kdl(config_path: &Path, config_str: &str) -> Result<Config, KdlConfigError> {
-    let result = knus::parse::<KdlConfig>(config_path.to_str().unwrap(), config_str)?;
+/// Read the files included by `file` (and the files they include), after `file` itself
+fn read_includes(
+    path: &Path,
+    file: KdlFile,
+    registry: &Registry,
+    files: &mut Vec<(PathBuf, KdlFile)>,
+) -> Result<(), KdlConfigError> {
+    let canonical = fs::canonicalize(path).unwrap_or(path.to_path_buf());
+    if files.iter().any(|(existing, _)| *existing == canonical) {
+        return Err(KdlConfigError::IncludedTwice(path.to_path_buf()));
+    }
+    let includes = file
+        .includes
+        .iter()
+        .map(|include| {
+            let base = path.parent().unwrap_or(Path::new("."));
+            expand_wildcards(&base.join(&include.path))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    files.push((canonical, file));
 
-    let version = result
+    for include in includes.into_iter().flatten() {
+        read_file(&include, registry, files)?;
+    }
+    Ok(())
+}
+
+fn read_file(
+    path: &Path,
+    registry: &Registry,
+    files: &mut Vec<(PathBuf, KdlFile)>,
+) -> Result<(), KdlConfigError> {
+    let config_str = fs::read_to_string(path).map_err(|source| KdlConfigError::ReadError {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let file = parse_file(&path.to_string_lossy(), &config_str, registry)?;
+    read_includes(path, file, registry, files)
+}
+
+/// Parse a single file, decoding the components of the registry along with the built-in ones
+fn parse_file(
+    file_name: &str,
+    config_str: &str,
+    registry: &Registry,
+) -> Result<KdlFile, KdlConfigError> {
+    let file = knus::parse_with_context::<ExpandedFile, Span, _>(file_name, config_str, |ctx| {
+        ctx.set(registry.clone())
+    })?;
+    let mut file = file.0;
+    if let Some(global) = &mut file.global {
+        let source = NamedSource::new(file_name, Arc::new(config_str.to_string()));
+        global.sources = vec![(0, source)];
+    }
+    Ok(file)
+}
+
+/// The files matching a path whose file name may contain wildcards, in lexical order
+/// A path without wildcards is returned as is, even if it does not exist
+fn expand_wildcards(path: &Path) -> Result<Vec<PathBuf>, KdlConfigError> {
+    let pattern = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    if !pattern.contains(['*', '?']) {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(source) => {
+            return Err(KdlConfigError::ReadError {
+                path: dir.to_path_buf(),
+                source,
+            })
+        }
+    };
+    let mut paths: Vec<_> = entries
+        .flatten()
+        .filter(|entry| glob_match(&pattern, &entry.file_name().to_string_lossy()))
+        .map(|entry| entry.path())
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// The global settings and the components of a configuration file and the files it includes
+/// The system configuration is read as a layer once, then either turned into a `Config`
+/// or combined with the configuration of a user, depending on its `user-config` setting
+pub struct Layer {
+    global: Option<GlobalNode>,
+    entries: Vec<ComponentEntry>,
+}
+
+impl Layer {
+    /// Read a configuration file, the files it includes and, with `conf_d`,
+    /// the fragments in this directory (in lexical order)
+    /// The components of all files are printed in the order the files are read,
+    /// and global settings set in a later file override the ones set in an earlier file
+    pub fn read(
+        config_path: &Path,
+        config_str: &str,
+        registry: &Registry,
+        conf_d: Option<&Path>,
+    ) -> Result<Self, KdlConfigError> {
+        let mut files = vec![];
+        let main_file = parse_file(&config_path.to_string_lossy(), config_str, registry)?;
+        read_includes(config_path, main_file, registry, &mut files)?;
+        if let Some(conf_d) = conf_d {
+            for fragment in expand_wildcards(&conf_d.join("*.kdl"))? {
+                read_file(&fragment, registry, &mut files)?;
+            }
+        }
+
+        let mut layer = Layer {
+            global: None,
+            entries: vec![],
+        };
+        for (_, file) in files {
+            layer.merge_global(file.global);
+            layer.entries.extend(file.components);
+        }
+        Ok(layer)
+    }
+
+    /// The `user-config` setting of the global settings, which must be valid
+    pub fn user_config(&self) -> Result<UserConfigPolicy, KdlConfigError> {
+        let global = self
+            .global
+            .as_ref()
+            .ok_or(KdlConfigError::NoVersion(EXPECTED_VERSION))?;
+        Ok(global.decode()?.user_config)
+    }
+
+    fn merge_global(&mut self, node: Option<GlobalNode>) {
+        match (&mut self.global, node) {
+            (Some(global), Some(node)) => global.merge(node),
+            (None, node) => self.global = node,
+            (_, None) => {}
+        }
+    }
+
+    /// Apply the configuration of a user on top of this one
+    /// The user's global settings override these ones, and the user's components are added
+    /// The user's `system` nodes are replaced by the components of this layer
+    /// (by default, they are printed before the user's components), except the ones hidden with `hide`
+    fn apply(&mut self, user: Layer) {
+        self.merge_global(user.global);
+
+        let hidden: Vec<String> = user
+            .entries
+            .iter()
+            .filter_map(|entry| match &entry.node {
+                ComponentNode::Hide(hide) => Some(hide.names.iter().cloned()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        let mut system: Vec<Option<ComponentEntry>> = self
+            .entries
+            .drain(..)
+            .filter(|entry| !hidden.iter().any(|name| entry.is_named(name)))
+            .map(Some)
+            .collect();
+        let mut take = |names: &[String]| -> Vec<ComponentEntry> {
+            system
+                .iter_mut()
+                .filter(|entry| {
+                    entry.as_ref().is_some_and(|entry| {
+                        names.is_empty() || names.iter().any(|name| entry.is_named(name))
+                    })
+                })
+                .filter_map(Option::take)
+                .collect()
+        };
+
+        // The components placed by name are taken first, so that a `system` without names
+        // only gets the remaining ones, wherever it is
+        let mut slots: Vec<Option<Vec<ComponentEntry>>> = vec![];
+        for entry in user.entries {
+            match entry.node {
+                ComponentNode::System(system) if system.names.is_empty() => slots.push(None),
+                ComponentNode::System(system) => slots.push(Some(take(&system.names))),
+                ComponentNode::Hide(_) => {}
+                _ => slots.push(Some(vec![entry])),
+            }
+        }
+        if !slots.iter().any(Option::is_none) {
+            slots.insert(0, None);
+        }
+        for slot in slots {
+            match slot {
+                Some(entries) => self.entries.extend(entries),
+                None => self.entries.extend(take(&[])),
+            }
+        }
+    }
+}
+
+/// Parse the main configuration file, the files it includes
+/// and, with `conf_d`, the fragments in this directory
+pub fn parse_kdl(
+    config_path: &Path,
+    config_str: &str,
+    registry: &Registry,
+    conf_d: Option<&Path>,
+) -> Result<Config, KdlConfigError> {
+    into_config(Layer::read(config_path, config_str, registry, conf_d)?)
+}
+
+/// Apply the configuration of a user on top of the system configuration
+/// (read with the fragments of `conf.d`)
+pub fn parse_layered_kdl(
+    mut system: Layer,
+    user_path: &Path,
+    user_str: &str,
+    registry: &Registry,
+) -> Result<Config, KdlConfigError> {
+    system.apply(Layer::read(user_path, user_str, registry, None)?);
+    into_config(system)
+}
+
+/// The `user-config` setting of the `global` node, on its own
+#[derive(knus::Decode)]
+struct UserConfigSetting {
+    #[knus(child, unwrap(argument), default)]
+    user_config: UserConfigPolicy,
+}
+
+/// The `user-config` setting of a configuration file, even if the rest of the file is invalid
+/// Only the file itself is read (not the files it includes or `conf.d`)
+/// Returns `None` if the setting cannot be read, like when the file is not valid KDL
+pub fn read_user_config_policy(config_path: &Path, config_str: &str) -> Option<UserConfigPolicy> {
+    let document = knus::parse_ast::<Span>(config_path.to_string_lossy(), config_str).ok()?;
+    let Some(global) = document
+        .nodes
+        .into_iter()
+        .rfind(|node| &**node.node_name == "global")
+    else {
+        return Some(UserConfigPolicy::default());
+    };
+    let global = global.map(|mut global| {
+        global.arguments.clear();
+        global.properties.clear();
+        if let Some(children) = global.children.as_mut() {
+            children.retain(|child| &**child.node_name == "user-config");
+        }
+        global
+    });
+    knus::decode::node::<UserConfigSetting, Span>(&global)
+        .ok()
+        .map(|setting| setting.user_config)
+}
+
+/// Decode the global settings of a layer and check the configuration as a whole
+pub fn into_config(layer: Layer) -> Result<Config, KdlConfigError> {
+    let global_node = layer
         .global
+        .ok_or(KdlConfigError::NoVersion(EXPECTED_VERSION))?;
+    let mut global = global_node.decode()?;
+    global.kdl = to_kdl(&global_node.node);
+
+    let version = global
         .version
         .clone()
         .ok_or(KdlConfigError::NoVersion(EXPECTED_VERSION))?;
@@ -74,32 +734,286 @@ pub fn parse_kdl(config_path: &Path, config_str: &str) -> Result<Config, KdlConf
         return Err(KdlConfigError::VersionMismatch(EXPECTED_VERSION, version));
     }
 
+    let mut components = vec![];
+    let mut rows = vec![];
+    for ComponentEntry {
+        node, options, kdl, ..
+    } in layer.entries
+    {
+        match node {
+            ComponentNode::Row(row) => {
+                let row_options = options;
+                let placement_row = rows.len();
+                let mut widths = vec![];
+                for (column_index, column) in row.columns.into_iter().enumerate() {
+                    widths.push(column.width);
+                    for ComponentEntry {
+                        node,
+                        mut options,
+                        kdl,
+                        ..
+                    } in column.components
+                    {
+                        // The options of a row apply to all of its components,
+                        // unless they set their own (the conditions of both must match)
+                        options.timeout = options.timeout.or(row_options.timeout);
+                        options.cache_ttl = options.cache_ttl.or(row_options.cache_ttl);
+                        options.when.extend(row_options.when.iter().cloned());
+                        components.push(ConfiguredComponent {
+                            component: into_component(node, &options)?,
+                            options,
+                            kdl,
+                            position: components.len() + 1,
+                            placement: Some(Placement {
+                                row: placement_row,
+                                column: column_index,
+                            }),
+                        });
+                    }
+                }
+                rows.push(RowLayout {
+                    gap: row.gap,
+                    columns: widths,
+                });
+            }
+            node => components.push(ConfiguredComponent {
+                component: into_component(node, &options)?,
+                options,
+                kdl,
+                position: components.len() + 1,
+                placement: None,
+            }),
+        }
+    }
+
+    let ids = components
+        .iter()
+        .filter_map(|configured| configured.options.id.as_ref());
+    if let Some(id) = ids.duplicates().next() {
+        return Err(KdlConfigError::DuplicateId(id.clone()));
+    }
+
     Ok(Config {
-        global: result.global,
-        components: result
-            .components
-            .into_iter()
-            .map(|x| match x {
-                ComponentNode::Command(command) => Box::new(command) as BoxedComponent,
-                ComponentNode::CgStats(stats) => Box::new(stats) as BoxedComponent,
-                ComponentNode::Docker(docker) => Box::new(docker) as BoxedComponent,
-                ComponentNode::DockerCompose(compose) => Box::new(compose) as BoxedComponent,
-                ComponentNode::Fail2ban(fail2ban) => Box::new(fail2ban) as BoxedComponent,
-                ComponentNode::Filesystems(filesystems) => Box::new(filesystems) as BoxedComponent,
-                ComponentNode::LastLogin(last_login) => Box::new(last_login) as BoxedComponent,
-                ComponentNode::LastRun(last_run) => Box::new(last_run) as BoxedComponent,
-                ComponentNode::LoadAvg(load_avg) => Box::new(load_avg) as BoxedComponent,
-                ComponentNode::Memory(memory) => Box::new(memory) as BoxedComponent,
-                ComponentNode::SSLCerts(certs) => Box::new(certs) as BoxedComponent,
-                ComponentNode::ServiceStatus(service_status) => {
-                    Box::new(service_status) as BoxedComponent
+        global,
+        components,
+        rows,
+    })
+}
+
+fn into_component(
+    node: ComponentNode,
+    options: &ComponentOptions,
+) -> Result<BoxedComponent, KdlConfigError> {
+    Ok(match node {
+        ComponentNode::Command(command) => Box::new(command),
+        ComponentNode::CgStats(stats) => Box::new(stats),
+        ComponentNode::Docker(docker) => Box::new(docker),
+        ComponentNode::DockerCompose(compose) => Box::new(compose),
+        ComponentNode::Fail2ban(fail2ban) => Box::new(fail2ban),
+        ComponentNode::Filesystems(filesystems) => Box::new(filesystems),
+        ComponentNode::LastLogin(last_login) => Box::new(last_login),
+        ComponentNode::LastRun(last_run) => Box::new(last_run),
+        ComponentNode::LoadAvg(load_avg) => Box::new(load_avg),
+        ComponentNode::Memory(memory) => Box::new(memory),
+        ComponentNode::Plugin(plugin) => Box::new(plugin),
+        ComponentNode::SSLCerts(certs) => Box::new(certs),
+        ComponentNode::ServiceStatus(service_status) => Box::new(service_status),
+        ComponentNode::UserServiceStatus(user_service_status) => Box::new(user_service_status),
+        ComponentNode::Summary(summary) => Box::new(summary),
+        ComponentNode::Uptime(uptime) => Box::new(uptime),
+        ComponentNode::Weather(mut weather) => {
+            // The weather component also uses the timeout for its HTTP request
+            if let Some(timeout) = options.timeout {
+                weather.timeout = timeout;
+            }
+            Box::new(weather)
+        }
+        ComponentNode::Row(_) => return Err(KdlConfigError::NestedRow),
+        ComponentNode::System(_) => return Err(KdlConfigError::LayerOnly("system")),
+        ComponentNode::Hide(_) => return Err(KdlConfigError::LayerOnly("hide")),
+        ComponentNode::Custom(component) => component,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_global() {
+        let parse = |config_str| knus::parse::<KdlFile>("test.kdl", config_str).unwrap();
+        let mut global =
+            parse(r#"global { version "1.0"; progress-width 30; progress-prefix "<"; }"#)
+                .global
+                .unwrap();
+        global.merge(parse(r#"global { progress-width 50; }"#).global.unwrap());
+
+        let global = global.decode().unwrap();
+        assert_eq!(global.version.as_deref(), Some("1.0"));
+        assert_eq!(global.progress_width.fallback(), 50);
+        assert_eq!(global.progress_prefix, "<");
+    }
+
+    #[test]
+    fn test_global_error_source() {
+        // Build the nodes without checking them, as if the files were only invalid once merged
+        let global = |file_name: &str, config_str: &str| GlobalNode {
+            node: knus::parse_ast::<Span>(file_name, config_str)
+                .unwrap()
+                .nodes[0]
+                .clone(),
+            sources: vec![(
+                0,
+                NamedSource::new(file_name, Arc::new(config_str.to_string())),
+            )],
+        };
+        let mut system = global("system.kdl", r#"global { version "1.0"; }"#);
+        system.merge(global("user.kdl", r#"global { progress-width "wide"; }"#));
+
+        let Err(KdlConfigError::GlobalError { errors }) = system.decode() else {
+            panic!("The merged settings should be invalid");
+        };
+        assert_eq!(errors.len(), 1);
+        let source = errors[0].source_code.as_ref().unwrap();
+        assert_eq!(source.name(), "user.kdl");
+        let label = &errors[0].labels[0];
+        let span = label.offset()..label.offset() + label.len();
+        assert_eq!(&source.inner()[span], r#""wide""#);
+    }
+
+    #[test]
+    fn test_to_kdl() {
+        let kdl = |config_str| {
+            let layer = Layer::read(Path::new("test.kdl"), config_str, &Registry::new(), None);
+            layer.unwrap().entries.remove(0).kdl
+        };
+        let kdl_a = kdl(r#"components { command "uptime" color="red" cache-ttl=60; }"#);
+        let kdl_b = kdl(r#"
+            components {
+                // The options shared by all components are not part of it
+                command color="red" id="uptime" "uptime" {
+                    when { user "alice"; }
                 }
-                ComponentNode::UserServiceStatus(user_service_status) => {
-                    Box::new(user_service_status) as BoxedComponent
+            }
+            "#);
+        assert_eq!(kdl_a, r#""command" "uptime" "color"="red""#);
+        assert_eq!(kdl_a, kdl_b);
+    }
+
+    #[test]
+    fn test_row_options() {
+        let config = parse_kdl(
+            Path::new("test.kdl"),
+            r#"
+            global { version "1.0"; }
+            components {
+                row timeout=5 cache-ttl=60 {
+                    column { uptime; }
+                    column { memory swap-pos="below" timeout=2; }
                 }
-                ComponentNode::Uptime(uptime) => Box::new(uptime) as BoxedComponent,
-                ComponentNode::Weather(weather) => Box::new(weather) as BoxedComponent,
-            })
-            .collect(),
-    })
+            }
+            "#,
+            &Registry::new(),
+            None,
+        )
+        .unwrap();
+        let options: Vec<_> = config
+            .components
+            .iter()
+            .map(|component| (component.options.timeout, component.options.cache_ttl))
+            .collect();
+        assert_eq!(options, [(Some(5), Some(60)), (Some(2), Some(60))]);
+
+        let Err(err) = parse_kdl(
+            Path::new("test.kdl"),
+            r#"global { version "1.0"; }; components { row id="top" { column { uptime; }; }; }"#,
+            &Registry::new(),
+            None,
+        ) else {
+            panic!("A row should not accept an id");
+        };
+        assert!(format!("{:?}", miette::Report::new(err)).contains("a row cannot have an `id`"));
+    }
+
+    #[test]
+    fn test_non_utf8_path() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"/etc/motd-\xff.kdl"));
+        let Err(err) = Layer::read(path, "components { nope; }", &Registry::new(), None) else {
+            panic!("The component should be unknown");
+        };
+        assert!(format!("{:?}", miette::Report::new(err)).contains("motd-\u{FFFD}.kdl"));
+    }
+
+    #[test]
+    fn test_read_user_config_policy() {
+        let policy = |config_str| read_user_config_policy(Path::new("test.kdl"), config_str);
+        assert_eq!(
+            policy(r#"global { version "2.0"; user-config "forbid"; }; components { nope; }"#),
+            Some(UserConfigPolicy::Forbid)
+        );
+        assert_eq!(
+            policy(r#"global { version "1.0"; }"#),
+            Some(UserConfigPolicy::Replace)
+        );
+        assert_eq!(policy(r#"global { user-config "everything"; }"#), None);
+        assert_eq!(policy(r#"global { user-config "forbid" "#), None);
+    }
+
+    #[test]
+    fn test_apply_user_layer() {
+        let layer = |config_str| {
+            Layer::read(Path::new("test.kdl"), config_str, &Registry::new(), None).unwrap()
+        };
+        let names = |layer: &Layer| {
+            layer
+                .entries
+                .iter()
+                .map(|entry| entry.name.clone())
+                .collect::<Vec<_>>()
+        };
+        let system = r#"components { uptime prefix="Up"; memory swap-pos="none"; load-avg format="{one}"; }"#;
+
+        let mut combined = layer(system);
+        combined.apply(layer(
+            r#"components { hide "load-avg"; memory swap-pos="below"; }"#,
+        ));
+        assert_eq!(names(&combined), ["uptime", "memory", "memory"]);
+
+        let mut combined = layer(system);
+        combined.apply(layer(
+            r#"components { system; uptime prefix="User"; system "memory"; }"#,
+        ));
+        assert_eq!(names(&combined), ["uptime", "load-avg", "uptime", "memory"]);
+
+        let system = r#"components { command "uptime" id="banner"; command "date"; }"#;
+        let mut combined = layer(system);
+        combined.apply(layer(r#"components { hide "banner"; }"#));
+        assert_eq!(names(&combined), ["command"]);
+
+        let duplicate = r#"
+            global { version "1.0"; }
+            components { uptime id="up"; memory swap-pos="none" id="up"; }
+        "#;
+        assert!(matches!(
+            parse_kdl(Path::new("test.kdl"), duplicate, &Registry::new(), None),
+            Err(KdlConfigError::DuplicateId(id)) if id == "up"
+        ));
+    }
+
+    #[test]
+    fn test_conf_d() {
+        let config_str = r#"global { version "1.0"; }; components { uptime; }"#;
+        let names = |conf_d: Option<&Path>| {
+            parse_kdl(Path::new("test.kdl"), config_str, &Registry::new(), conf_d)
+                .unwrap()
+                .components
+                .iter()
+                .map(|configured| configured.component.name())
+                .collect::<Vec<_>>()
+        };
+        let conf_d = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/conf.d");
+        assert_eq!(names(Some(&conf_d)), ["uptime", "memory"]);
+        assert_eq!(names(None), ["uptime"]);
+    }
 }
@@ -0,0 +1,407 @@
+use indexmap::IndexMap;
+use serde::de::{Deserialize, Visitor};
+use std::fmt::Write;
+
+use crate::config::expand::{is_expanded, Facts, EXPANDED_ARGUMENTS};
+use crate::config::toml_config::{Fields, TomlConfigError};
+use crate::config::Config;
+
+/// Keys of the legacy configuration whose values are colours
+/// The names of colours are written in snake case in TOML (`light_red`) and kebab case in KDL
+const COLOR_KEYS: [&str; 5] = ["color", "ok", "warn", "critical", "muted"];
+
+/// Keys of the legacy configuration whose values are variants of an enum
+/// TOML also accepts capitalized variants (`Beside`), but KDL only accepts lowercase ones
+const ENUM_KEYS: [&str; 3] = ["swap_pos", "style", "sort_method"];
+
+/// Convert a legacy TOML configuration to an equivalent KDL configuration
+/// The components are written in the same order, each with a comment naming its TOML section
+pub fn migrate_toml(config_str: &str) -> Result<String, TomlConfigError> {
+    // Only valid configurations are migrated, using the same structures as `parse_toml`
+    toml::from_str::<Config>(config_str)?;
+    let Migration { global, components } = toml::from_str(config_str)?;
+
+    let mut kdl =
+        "// Migrated from the legacy TOML configuration by `rust-motd migrate`\n".to_string();
+    kdl += "global {\n  version \"1.0\"\n";
+    for (key, value) in global {
+        match key.as_str() {
+            "show_legacy_warning" => {
+                kdl += "  // `show_legacy_warning` is only used by the TOML configuration\n"
+            }
+            _ => write_node(&mut kdl, 1, &key, &value),
+        }
+    }
+    kdl += "}\n\ncomponents {\n";
+    kdl += &components.join("\n");
+    kdl += "}\n";
+    Ok(kdl)
+}
+
+/// The sections of a legacy configuration, already converted to KDL
+struct Migration {
+    global: IndexMap<String, toml::Value>,
+    components: Vec<String>,
+}
+
+/// Properties of a section, in the order they are written
+type Properties = IndexMap<String, toml::Value>;
+
+/// The `[ssl_certificates]` section, whose certificates are a table of their own
+#[derive(serde::Deserialize)]
+struct SSLCertsSection {
+    #[serde(flatten)]
+    properties: Properties,
+    certs: IndexMap<String, String>,
+}
+
+// Deserialized like `Config`, so that the components stay in the order of the configuration file
+impl<'de> Deserialize<'de> for Migration {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct MigrationVisitor;
+
+        impl<'de> Visitor<'de> for MigrationVisitor {
+            type Value = Migration;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("struct Config")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut global = IndexMap::new();
+                let mut components = vec![];
+
+                while let Some(key) = map.next_key()? {
+                    let mut kdl = String::new();
+                    match key {
+                        Fields::Global => {
+                            global = map.next_value()?;
+                            continue;
+                        }
+                        Fields::Command => {
+                            let mut properties: Properties = map.next_value()?;
+                            let command = properties.shift_remove("command");
+                            kdl += &section("banner", "command", &properties);
+                            if let Some(command) = command {
+                                write!(kdl, " {}", scalar("command", &command)).unwrap();
+                            }
+                            kdl += "\n";
+                        }
+                        Fields::CgStats => {
+                            kdl += &section("cg_stats", "cg-stats", &map.next_value()?);
+                            kdl += "\n";
+                        }
+                        Fields::Docker => {
+                            let containers: IndexMap<String, String> = map.next_value()?;
+                            kdl += &section("docker", "docker", &Properties::new());
+                            kdl +=
+                                &children(containers.iter().map(|(docker_name, display_name)| {
+                                    format!(
+                                        "container docker-name={} display-name={}",
+                                        string(docker_name),
+                                        expanded(display_name)
+                                    )
+                                }));
+                        }
+                        Fields::DockerCompose => {
+                            let stacks: IndexMap<String, String> = map.next_value()?;
+                            kdl += &section("docker_compose", "docker-compose", &Properties::new());
+                            kdl += &children(stacks.iter().map(|(path, display_name)| {
+                                format!(
+                                    "stack path={} display-name={}",
+                                    expanded(path),
+                                    expanded(display_name)
+                                )
+                            }));
+                        }
+                        Fields::Fail2Ban => {
+                            let mut properties: IndexMap<String, Vec<String>> = map.next_value()?;
+                            let jails = properties.shift_remove("jails").unwrap_or_default();
+                            kdl += &section("fail_2_ban", "fail2ban", &Properties::new());
+                            kdl += &children(
+                                jails.iter().map(|jail| format!("jail {}", string(jail))),
+                            );
+                        }
+                        Fields::Filesystems => {
+                            let mounts: IndexMap<String, String> = map.next_value()?;
+                            kdl += &section("filesystems", "filesystems", &Properties::new());
+                            kdl += &children(mounts.iter().map(|(name, mount_point)| {
+                                format!(
+                                    "filesystem name={} mount-point={}",
+                                    expanded(name),
+                                    expanded(mount_point)
+                                )
+                            }));
+                        }
+                        Fields::LastLogin => {
+                            let users: IndexMap<String, usize> = map.next_value()?;
+                            kdl += &section("last_login", "last-login", &Properties::new());
+                            kdl += &children(users.iter().map(|(username, num_logins)| {
+                                format!(
+                                    "user username={} num-logins={num_logins}",
+                                    string(username)
+                                )
+                            }));
+                        }
+                        Fields::LastRun => {
+                            kdl += &section("last_run", "last-run", &map.next_value()?);
+                            kdl += "\n";
+                        }
+                        Fields::LoadAvg => {
+                            kdl += &section("load_avg", "load-avg", &map.next_value()?);
+                            kdl += "\n";
+                        }
+                        Fields::Memory => {
+                            kdl += &section("memory", "memory", &map.next_value()?);
+                            kdl += "\n";
+                        }
+                        Fields::ServiceStatus | Fields::UserServiceStatus => {
+                            let (toml_name, name) = match key {
+                                Fields::ServiceStatus => ("service_status", "service-status"),
+                                _ => ("user_service_status", "user-service-status"),
+                            };
+                            let services: IndexMap<String, String> = map.next_value()?;
+                            kdl += &section(toml_name, name, &Properties::new());
+                            kdl += &children(services.iter().map(|(display_name, unit)| {
+                                format!(
+                                    "service display-name={} unit={}",
+                                    expanded(display_name),
+                                    string(unit)
+                                )
+                            }));
+                        }
+                        Fields::SSLCerts => {
+                            let certs: SSLCertsSection = map.next_value()?;
+                            kdl += &section("ssl_certificates", "ssl-certs", &certs.properties);
+                            kdl += &children(certs.certs.iter().map(|(name, path)| {
+                                format!("cert name={} path={}", expanded(name), expanded(path))
+                            }));
+                        }
+                        Fields::Uptime => {
+                            kdl += &section("uptime", "uptime", &map.next_value()?);
+                            kdl += "\n";
+                        }
+                        Fields::Weather => {
+                            kdl += &section("weather", "weather", &map.next_value()?);
+                            kdl += "\n";
+                        }
+                    }
+                    components.push(kdl);
+                }
+
+                Ok(Migration { global, components })
+            }
+        }
+
+        deserializer.deserialize_map(MigrationVisitor)
+    }
+}
+
+/// The comment and the start of the node of a component (without a line break)
+fn section(toml_name: &str, name: &str, properties: &Properties) -> String {
+    let mut kdl = format!("  // Migrated from [{toml_name}]\n  {name}");
+    for (key, value) in properties {
+        write!(kdl, " {}={}", key.replace('_', "-"), scalar(key, value)).unwrap();
+    }
+    kdl
+}
+
+/// The children of a component node, and the end of the node
+fn children(children: impl Iterator<Item = String>) -> String {
+    let mut kdl = " {\n".to_string();
+    for child in children {
+        writeln!(kdl, "    {child}").unwrap();
+    }
+    kdl + "  }\n"
+}
+
+/// Write a setting of `global` as a node, with its children if it is a table (like `theme`)
+fn write_node(kdl: &mut String, depth: usize, key: &str, value: &toml::Value) {
+    let indent = "  ".repeat(depth);
+    let name = key.replace('_', "-");
+    match value {
+        toml::Value::Table(table) => {
+            writeln!(kdl, "{indent}{name} {{").unwrap();
+            for (key, value) in table {
+                write_node(kdl, depth + 1, key, value);
+            }
+            writeln!(kdl, "{indent}}}").unwrap();
+        }
+        value => writeln!(kdl, "{indent}{name} {}", scalar(key, value)).unwrap(),
+    }
+}
+
+/// A value in KDL syntax
+fn scalar(key: &str, value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(value) if COLOR_KEYS.contains(&key) && !value.starts_with('#') => {
+            string(&value.replace('_', "-"))
+        }
+        toml::Value::String(value) if ENUM_KEYS.contains(&key) => string(&value.to_lowercase()),
+        toml::Value::String(value) if is_expanded_key(key) => expanded(value),
+        toml::Value::String(value) => string(value),
+        toml::Value::Integer(value) => value.to_string(),
+        // KDL only accepts floats with a decimal point, which `Debug` always prints
+        toml::Value::Float(value) => format!("{value:?}"),
+        toml::Value::Boolean(value) => value.to_string(),
+        toml::Value::Datetime(value) => string(&value.to_string()),
+        // Arrays and tables are only used by the sections that have children
+        toml::Value::Array(_) | toml::Value::Table(_) => string(&value.to_string()),
+    }
+}
+
+/// Whether the value of a legacy key is expanded in KDL, as an argument (`command`) or a property
+fn is_expanded_key(key: &str) -> bool {
+    let key = key.replace('_', "-");
+    is_expanded(&key) || EXPANDED_ARGUMENTS.contains(&key.as_str())
+}
+
+/// A string in KDL syntax that `Facts::expand` gives back as is,
+/// since legacy values that are expanded in KDL (like a `~` or `${HOME}`) were used literally
+fn expanded(value: &str) -> String {
+    string(&Facts::escape(value))
+}
+
+/// A string in KDL syntax
+/// Strings with line breaks or backslashes (like shell commands) are written as raw strings
+/// so that they stay readable
+fn string(value: &str) -> String {
+    if value.contains(['\n', '\\']) {
+        let hashes = (0..)
+            .map(|count| "#".repeat(count))
+            .find(|hashes| !value.contains(&format!("\"{hashes}")))
+            .unwrap();
+        return format!("r{hashes}\"{value}\"{hashes}");
+    }
+    let mut result = "\"".to_string();
+    for c in value.chars() {
+        match c {
+            '"' => result += "\\\"",
+            '\\' => result += "\\\\",
+            '\n' => result += "\\n",
+            '\r' => result += "\\r",
+            '\t' => result += "\\t",
+            c => result.push(c),
+        }
+    }
+    result + "\""
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::kdl_config::parse_kdl;
+    use crate::config::registry::Registry;
+    use crate::config::toml_config::parse_toml;
+    use std::path::Path;
+
+    #[test]
+    fn test_migrate() {
+        let toml = r#"
+            [global]
+            progress_prefix = "<"
+            show_legacy_warning = false
+
+            [global.theme]
+            muted = "light_black"
+
+            [banner]
+            color = "light_red"
+            command = """
+            echo '\\o/' &&
+            echo "done"
+            """
+
+            [filesystems]
+            root = "/"
+            home = "/home"
+
+            [memory]
+            swap_pos = "Beside"
+
+            [cg_stats]
+            state_file = "cg_stats.toml"
+            threshold = 1.0
+
+            [fail_2_ban]
+            jails = ["sshd", "nginx"]
+
+            [ssl_certificates]
+            sort_method = "manual"
+
+                [ssl_certificates.certs]
+                Second = "/second.pem"
+                First = "/first.pem"
+
+            [uptime]
+            prefix = "Up"
+        "#;
+        let kdl = migrate_toml(toml).unwrap();
+        assert!(kdl.contains("command color=\"light-red\" r#\"            echo '\\o/' &&\n"));
+        assert!(kdl.contains("memory swap-pos=\"beside\"\n"));
+        assert!(kdl.contains(
+            "cert name=\"Second\" path=\"/second.pem\"\n    cert name=\"First\" path=\"/first.pem\""
+        ));
+
+        let migrated = parse_kdl(Path::new("migrated.kdl"), &kdl, &Registry::new(), None).unwrap();
+        let legacy = parse_toml(toml).unwrap();
+        let names = |config: &Config| {
+            config
+                .components
+                .iter()
+                .map(|component| format!("{:?}", component.component))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&migrated), names(&legacy));
+        assert_eq!(migrated.global.progress_prefix, "<");
+    }
+
+    #[test]
+    fn test_migrate_escapes() {
+        let toml = r#"
+            [global]
+            progress_empty_character = "~"
+            progress_prefix = "{user}"
+
+            [banner]
+            color = "red"
+            command = "echo ${HOME} $${HOME} {hostname} $ {word}"
+
+            [filesystems]
+            "~" = "/"
+
+            [ssl_certificates]
+                [ssl_certificates.certs]
+                "{user}" = "~/{hostname}.pem"
+
+            [uptime]
+            prefix = "~~ {{uid}}"
+        "#;
+        let kdl = migrate_toml(toml).unwrap();
+        assert!(kdl.contains("progress-empty-character \"~\"\n"));
+        assert!(kdl.contains("progress-prefix \"{user}\"\n"));
+        assert!(
+            kdl.contains("command color=\"red\" \"echo $${HOME} $$${HOME} {{hostname}} $ {word}\"")
+        );
+        assert!(kdl.contains("cert name=\"{{user}}\" path=\"~~/{{hostname}}.pem\""));
+
+        let migrated = parse_kdl(Path::new("migrated.kdl"), &kdl, &Registry::new(), None).unwrap();
+        let legacy = parse_toml(toml).unwrap();
+        let components = |config: &Config| {
+            config
+                .components
+                .iter()
+                .map(|component| format!("{:?}", component.component))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(components(&migrated), components(&legacy));
+        assert_eq!(migrated.global.progress_empty_character, "~");
+        assert_eq!(migrated.global.progress_prefix, "{user}");
+    }
+}
@@ -1,9 +1,15 @@
+pub mod expand;
 pub mod get_config;
 pub mod global_config;
 pub mod kdl_config;
+pub mod migrate;
+pub mod registry;
+pub mod theme;
 pub mod toml_config;
+pub mod when;
 
-use crate::component::BoxedComponent;
+use crate::component::ConfiguredComponent;
+use crate::layout::RowLayout;
 use global_config::GlobalConfig;
 
 /// Configuration for all components and the global settings
@@ -11,6 +17,8 @@ use global_config::GlobalConfig;
 /// and is the order in which they should be printed
 /// This way, users can configure the order of components by shifting lines in the config file
 pub struct Config {
-    pub components: Vec<BoxedComponent>,
+    pub components: Vec<ConfiguredComponent>,
     pub global: GlobalConfig,
+    /// The `row` nodes of the configuration, the components they contain have a `Placement`
+    pub rows: Vec<RowLayout>,
 }
@@ -0,0 +1,80 @@
+use knus::ast::SpannedNode;
+use knus::decode::Context;
+use knus::errors::DecodeError;
+use knus::span::Span;
+use std::collections::HashMap;
+
+use crate::component::{BoxedComponent, Component};
+
+/// Decodes a node of `components` into a component
+type DecodeComponent =
+    fn(&SpannedNode<Span>, &mut Context<Span>) -> Result<BoxedComponent, DecodeError<Span>>;
+
+/// Components defined outside of `rust-motd`, by the name of their node in `components`
+/// They can be used in the KDL configuration like the built-in components,
+/// including the options that every component accepts (like `timeout` and `when`)
+#[derive(Clone, Default)]
+pub struct Registry {
+    components: HashMap<String, DecodeComponent>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make a component available as a node called `name`, like `my-component`
+    /// A registered component takes precedence over a built-in component with the same name
+    pub fn register<T>(&mut self, name: &str) -> &mut Self
+    where
+        T: knus::Decode<Span> + Component + Send + 'static,
+    {
+        self.components
+            .insert(name.to_string(), decode_component::<T>);
+        self
+    }
+
+    pub(crate) fn decoder(&self, name: &str) -> Option<DecodeComponent> {
+        self.components.get(name).copied()
+    }
+}
+
+fn decode_component<T>(
+    node: &SpannedNode<Span>,
+    ctx: &mut Context<Span>,
+) -> Result<BoxedComponent, DecodeError<Span>>
+where
+    T: knus::Decode<Span> + Component + Send + 'static,
+{
+    Ok(Box::new(T::decode_node(node, ctx)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::uptime::Uptime;
+    use crate::config::kdl_config::parse_kdl;
+    use std::path::Path;
+
+    #[test]
+    fn test_registered_component() {
+        let config_str = r#"
+            global { version "1.0"; }
+            components {
+                up-since prefix="Since" timeout=5
+                row { column { up-since; }; }
+            }
+        "#;
+        let mut registry = Registry::new();
+        registry.register::<Uptime>("up-since");
+        let config = parse_kdl(Path::new("test.kdl"), config_str, &registry, None).unwrap();
+        let components: Vec<_> = config
+            .components
+            .iter()
+            .map(|configured| (configured.component.name(), configured.options.timeout))
+            .collect();
+        assert_eq!(components, [("uptime", Some(5)), ("uptime", None)]);
+
+        assert!(parse_kdl(Path::new("test.kdl"), config_str, &Registry::new(), None).is_err());
+    }
+}
@@ -0,0 +1,255 @@
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+use termion::color;
+
+/// Percentage of usage above which bars are printed in the warn colour, by default
+pub const DEFAULT_WARN: usize = 75;
+/// Percentage of usage above which bars are printed in the critical colour, by default
+pub const DEFAULT_CRITICAL: usize = 95;
+
+pub fn default_warn() -> usize {
+    DEFAULT_WARN
+}
+
+pub fn default_critical() -> usize {
+    DEFAULT_CRITICAL
+}
+
+/// The 16 colours every terminal supports
+#[derive(knus::DecodeScalar, Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum NamedColor {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    LightBlack,
+    LightRed,
+    LightGreen,
+    LightYellow,
+    LightBlue,
+    LightMagenta,
+    LightCyan,
+    LightWhite,
+}
+
+/// A colour in the configuration file
+/// Either a name (`"light-red"`), a number of the 256-colour palette (`208`)
+/// or a truecolor value (`"#ff8700"`)
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(try_from = "ColorValue")]
+pub enum Color {
+    Named(NamedColor),
+    Ansi256(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// The escape sequence setting this colour as the foreground colour
+    pub fn fg(self) -> String {
+        match self {
+            Color::Named(named) => match named {
+                NamedColor::Black => color::Black.fg_str(),
+                NamedColor::Red => color::Red.fg_str(),
+                NamedColor::Green => color::Green.fg_str(),
+                NamedColor::Yellow => color::Yellow.fg_str(),
+                NamedColor::Blue => color::Blue.fg_str(),
+                NamedColor::Magenta => color::Magenta.fg_str(),
+                NamedColor::Cyan => color::Cyan.fg_str(),
+                NamedColor::White => color::White.fg_str(),
+                NamedColor::LightBlack => color::LightBlack.fg_str(),
+                NamedColor::LightRed => color::LightRed.fg_str(),
+                NamedColor::LightGreen => color::LightGreen.fg_str(),
+                NamedColor::LightYellow => color::LightYellow.fg_str(),
+                NamedColor::LightBlue => color::LightBlue.fg_str(),
+                NamedColor::LightMagenta => color::LightMagenta.fg_str(),
+                NamedColor::LightCyan => color::LightCyan.fg_str(),
+                NamedColor::LightWhite => color::LightWhite.fg_str(),
+            }
+            .to_string(),
+            Color::Ansi256(value) => color::AnsiValue(value).fg_string(),
+            Color::Rgb(r, g, b) => color::Rgb(r, g, b).fg_string(),
+        }
+    }
+}
+
+/// Parse a `#rrggbb` truecolor value
+fn parse_hex(value: &str) -> Option<Color> {
+    let hex = value.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?))
+}
+
+impl<S: knus::traits::ErrorSpan> knus::DecodeScalar<S> for Color {
+    fn type_check(
+        type_name: &Option<knus::span::Spanned<knus::ast::TypeName, S>>,
+        ctx: &mut knus::decode::Context<S>,
+    ) {
+        <NamedColor as knus::DecodeScalar<S>>::type_check(type_name, ctx);
+    }
+
+    fn raw_decode(
+        value: &knus::span::Spanned<knus::ast::Literal, S>,
+        ctx: &mut knus::decode::Context<S>,
+    ) -> Result<Self, knus::errors::DecodeError<S>> {
+        match &**value {
+            knus::ast::Literal::Int(_) => {
+                <u8 as knus::DecodeScalar<S>>::raw_decode(value, ctx).map(Color::Ansi256)
+            }
+            knus::ast::Literal::String(name) if name.starts_with('#') => parse_hex(name)
+                .ok_or_else(|| {
+                    knus::errors::DecodeError::conversion(
+                        value,
+                        "expected a color like \"#ff8700\"",
+                    )
+                }),
+            _ => <NamedColor as knus::DecodeScalar<S>>::raw_decode(value, ctx).map(Color::Named),
+        }
+    }
+}
+
+/// How a `Color` is written in the TOML configuration
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorValue {
+    Ansi256(u8),
+    Name(String),
+}
+
+impl TryFrom<ColorValue> for Color {
+    type Error = String;
+
+    fn try_from(value: ColorValue) -> Result<Self, Self::Error> {
+        match value {
+            ColorValue::Ansi256(value) => Ok(Color::Ansi256(value)),
+            ColorValue::Name(name) if name.starts_with('#') => parse_hex(&name).ok_or(format!(
+                "expected a color like \"#ff8700\", found \"{name}\""
+            )),
+            ColorValue::Name(name) => NamedColor::deserialize(name.as_str().into_deserializer())
+                .map(Color::Named)
+                .map_err(|err: serde::de::value::Error| err.to_string()),
+        }
+    }
+}
+
+/// How bad something is, which decides the colour it is printed in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Ok,
+    Warn,
+    Critical,
+}
+
+impl Level {
+    /// The level of a usage ratio (between 0 and 1) given the warn and critical percentages
+    pub fn from_usage(ratio: f64, warn: usize, critical: usize) -> Level {
+        match (ratio * 100.0) as usize {
+            percent if percent > critical => Level::Critical,
+            percent if percent > warn => Level::Warn,
+            _ => Level::Ok,
+        }
+    }
+
+    /// The problem with the warn and critical percentages of a component, for `rust-motd check`
+    /// Past 100% or below the warn percentage, the critical percentage could never be reached
+    pub fn check_thresholds(warn: usize, critical: usize) -> Option<String> {
+        if critical > 100 {
+            Some(format!("critical={critical} is more than 100%"))
+        } else if warn >= critical {
+            Some(format!("warn={warn} is not lower than critical={critical}"))
+        } else {
+            None
+        }
+    }
+}
+
+/// The colours used by all components
+#[derive(knus::Decode, Debug, Deserialize)]
+pub struct Theme {
+    #[knus(child, unwrap(argument), default = default_ok())]
+    #[serde(default = "default_ok")]
+    pub ok: Color,
+
+    #[knus(child, unwrap(argument), default = default_warn_color())]
+    #[serde(default = "default_warn_color")]
+    pub warn: Color,
+
+    #[knus(child, unwrap(argument), default = default_critical_color())]
+    #[serde(default = "default_critical_color")]
+    pub critical: Color,
+
+    /// Used for things that are unimportant, like the empty part of progress bars
+    #[knus(child, unwrap(argument), default = default_muted())]
+    #[serde(default = "default_muted")]
+    pub muted: Color,
+}
+
+impl Theme {
+    /// The escape sequence for the colour of a level
+    pub fn level(&self, level: Level) -> String {
+        match level {
+            Level::Ok => self.ok.fg(),
+            Level::Warn => self.warn.fg(),
+            Level::Critical => self.critical.fg(),
+        }
+    }
+
+    pub fn muted(&self) -> String {
+        self.muted.fg()
+    }
+}
+
+fn default_ok() -> Color {
+    Color::Named(NamedColor::Green)
+}
+
+fn default_warn_color() -> Color {
+    Color::Named(NamedColor::Yellow)
+}
+
+fn default_critical_color() -> Color {
+    Color::Named(NamedColor::Red)
+}
+
+fn default_muted() -> Color {
+    Color::Named(NamedColor::LightBlack)
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            ok: default_ok(),
+            warn: default_warn_color(),
+            critical: default_critical_color(),
+            muted: default_muted(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_thresholds() {
+        assert_eq!(
+            Level::check_thresholds(DEFAULT_WARN, DEFAULT_CRITICAL),
+            None
+        );
+        assert_eq!(
+            Level::check_thresholds(90, 80).as_deref(),
+            Some("warn=90 is not lower than critical=80")
+        );
+        assert_eq!(
+            Level::check_thresholds(50, 150).as_deref(),
+            Some("critical=150 is more than 100%")
+        );
+    }
+}
@@ -1,8 +1,10 @@
 use indexmap::IndexMap;
 use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use std::io::{self, IsTerminal};
 use termion::{color, style};
 use thiserror::Error;
 
+use crate::component::{BoxedComponent, ComponentOptions, ConfiguredComponent};
 use crate::components::cg_stats::CgStats;
 use crate::components::command::Command;
 use crate::components::docker::{Docker, DockerContainer};
@@ -24,7 +26,7 @@ use crate::config::Config;
 /// This includes all components plus the global configuration settings
 #[derive(Debug, serde::Deserialize)]
 #[serde(field_identifier, rename_all = "snake_case")]
-enum Fields {
+pub enum Fields {
     Global,
     // Command component was called banner in the legacy configuration format.
     #[serde(rename = "banner")]
@@ -73,28 +75,22 @@ impl<'de> Deserialize<'de> for Config {
             where
                 A: serde::de::MapAccess<'de>,
             {
-                let mut result = Config {
-                    components: vec![],
-                    global: GlobalConfig::default(),
-                };
+                let mut components: Vec<BoxedComponent> = vec![];
+                let mut global = GlobalConfig::default();
 
                 while let Some(key) = map.next_key()? {
                     match key {
                         Fields::Global => {
-                            result.global = map.next_value()?;
+                            global = map.next_value()?;
                         }
                         Fields::Command => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<Command>()?));
+                            components.push(Box::new(map.next_value::<Command>()?));
                         }
                         Fields::CgStats => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<CgStats>()?));
+                            components.push(Box::new(map.next_value::<CgStats>()?));
                         }
                         Fields::Docker => {
-                            result.components.push(Box::new(Docker::new(
+                            components.push(Box::new(Docker::new(
                                 map.next_value::<IndexMap<String, String>>()?
                                     .into_iter()
                                     .map(|(docker_name, display_name)| DockerContainer {
@@ -105,7 +101,7 @@ impl<'de> Deserialize<'de> for Config {
                             )));
                         }
                         Fields::DockerCompose => {
-                            result.components.push(Box::new(DockerCompose::new(
+                            components.push(Box::new(DockerCompose::new(
                                 map.next_value::<IndexMap<String, String>>()?
                                     .into_iter()
                                     .map(|(path, display_name)| ComposeStack { path, display_name })
@@ -113,12 +109,10 @@ impl<'de> Deserialize<'de> for Config {
                             )));
                         }
                         Fields::Fail2Ban => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<Fail2Ban>()?));
+                            components.push(Box::new(map.next_value::<Fail2Ban>()?));
                         }
                         Fields::Filesystems => {
-                            result.components.push(Box::new(Filesystems::new(
+                            components.push(Box::new(Filesystems::new(
                                 map.next_value::<IndexMap<String, String>>()?
                                     .into_iter()
                                     .map(|(name, mount_point)| Mount { name, mount_point })
@@ -126,7 +120,7 @@ impl<'de> Deserialize<'de> for Config {
                             )));
                         }
                         Fields::LastLogin => {
-                            result.components.push(Box::new(LastLogin {
+                            components.push(Box::new(LastLogin {
                                 users: map
                                     .next_value::<IndexMap<String, usize>>()?
                                     .into_iter()
@@ -138,22 +132,16 @@ impl<'de> Deserialize<'de> for Config {
                             }));
                         }
                         Fields::LastRun => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<LastRun>()?));
+                            components.push(Box::new(map.next_value::<LastRun>()?));
                         }
                         Fields::LoadAvg => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<LoadAvg>()?));
+                            components.push(Box::new(map.next_value::<LoadAvg>()?));
                         }
                         Fields::Memory => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<Memory>()?));
+                            components.push(Box::new(map.next_value::<Memory>()?));
                         }
                         Fields::ServiceStatus => {
-                            result.components.push(Box::new(ServiceStatus {
+                            components.push(Box::new(ServiceStatus {
                                 services: map
                                     .next_value::<IndexMap<String, String>>()?
                                     .into_iter()
@@ -162,7 +150,7 @@ impl<'de> Deserialize<'de> for Config {
                             }));
                         }
                         Fields::UserServiceStatus => {
-                            result.components.push(Box::new(UserServiceStatus {
+                            components.push(Box::new(UserServiceStatus {
                                 services: map
                                     .next_value::<IndexMap<String, String>>()?
                                     .into_iter()
@@ -171,23 +159,32 @@ impl<'de> Deserialize<'de> for Config {
                             }));
                         }
                         Fields::SSLCerts => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<SSLCerts>()?));
+                            components.push(Box::new(map.next_value::<SSLCerts>()?));
                         }
                         Fields::Uptime => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<Uptime>()?));
+                            components.push(Box::new(map.next_value::<Uptime>()?));
                         }
                         Fields::Weather => {
-                            result
-                                .components
-                                .push(Box::new(map.next_value::<Weather>()?));
+                            components.push(Box::new(map.next_value::<Weather>()?));
                         }
                     }
                 }
-                Ok(result)
+                // The TOML configuration does not support the generic component options
+                Ok(Config {
+                    components: components
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, component)| ConfiguredComponent {
+                            component,
+                            options: ComponentOptions::default(),
+                            kdl: String::new(),
+                            position: index + 1,
+                            placement: None,
+                        })
+                        .collect(),
+                    global,
+                    rows: vec![],
+                })
             }
         }
 
@@ -232,17 +229,23 @@ pub fn parse_toml(config_str: &str) -> Result<Config, TomlConfigError> {
     let config: Config = toml::from_str(config_str)?;
 
     if config.global.show_legacy_warning {
-        println!(
+        let (warning_color, warning_reset) =
+            match config.global.color.enabled(io::stderr().is_terminal()) {
+                true => (color::Yellow.fg_str(), style::Reset.as_ref()),
+                false => ("", ""),
+            };
+        // Printed to stderr so that it does not end up in `--format json` output
+        eprintln!(
             concat!(
             "{}You are using the legacy TOML configuration format.\n",
             "Support may be removed in the next major release.\n",
             "Please upgrade to the KDL configuration format ",
             "(see https://github.com/rust-motd/rust-motd/tree/main/docs/config_migration.md).\n",
+            "Run `rust-motd migrate config.toml > config.kdl` to convert your configuration.\n",
             "Add `show_legacy_warning = false` to the `[global]` section ",
             "of your TOML config to silence this.{}",
         ),
-            color::Yellow.fg_str(),
-            style::Reset
+            warning_color, warning_reset
         );
     }
 
@@ -0,0 +1,178 @@
+use chrono::{Datelike, Local};
+use std::env;
+use std::ffi::{CStr, CString};
+use std::path::Path;
+
+use crate::components::service_status::unit_exists;
+use crate::sysroot::get_username_by_uid;
+
+/// A day of the week in a `when` condition
+#[derive(knus::DecodeScalar, Debug, Clone, Copy, PartialEq)]
+pub enum Day {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl From<chrono::Weekday> for Day {
+    fn from(weekday: chrono::Weekday) -> Self {
+        match weekday {
+            chrono::Weekday::Mon => Day::Mon,
+            chrono::Weekday::Tue => Day::Tue,
+            chrono::Weekday::Wed => Day::Wed,
+            chrono::Weekday::Thu => Day::Thu,
+            chrono::Weekday::Fri => Day::Fri,
+            chrono::Weekday::Sat => Day::Sat,
+            chrono::Weekday::Sun => Day::Sun,
+        }
+    }
+}
+
+/// Conditions that must all be true for a component to be printed
+/// Conditions taking several values are true if any of the values matches
+#[derive(knus::Decode, Debug, Clone, Default)]
+pub struct When {
+    /// The name of the user running `rust-motd`
+    #[knus(child, unwrap(arguments), default)]
+    user: Vec<String>,
+    /// A group the user running `rust-motd` is a member of
+    #[knus(child, unwrap(arguments), default)]
+    group: Vec<String>,
+    /// The hostname, with `*` and `?` wildcards
+    #[knus(child, unwrap(arguments), default)]
+    host: Vec<String>,
+    /// Whether this is an SSH session (`SSH_CONNECTION` is set)
+    #[knus(child, unwrap(argument))]
+    ssh: Option<bool>,
+    /// A file or directory that exists
+    #[knus(child, unwrap(arguments), default)]
+    file: Vec<String>,
+    /// A systemd unit that exists
+    #[knus(child, unwrap(arguments), default)]
+    unit: Vec<String>,
+    /// The current day of the week
+    #[knus(child, unwrap(arguments), default)]
+    day: Vec<Day>,
+}
+
+impl When {
+    /// Whether the component should be printed
+    /// The conditions are only checked if they are set, so that no time is wasted on the others
+    pub fn matches(&self) -> bool {
+        let any = |values: &[String], check: &dyn Fn(&str) -> bool| {
+            values.is_empty() || values.iter().any(|value| check(value))
+        };
+        any(&self.user, &|user| current_user().as_deref() == Some(user))
+            && any(&self.group, &is_member_of)
+            && any(&self.host, &|pattern| {
+                hostname().is_some_and(|hostname| glob_match(pattern, &hostname))
+            })
+            && self
+                .ssh
+                .is_none_or(|ssh| env::var_os("SSH_CONNECTION").is_some() == ssh)
+            && any(&self.file, &|file| Path::new(file).exists())
+            && any(&self.unit, &|unit| unit_exists(unit, false))
+            && (self.day.is_empty() || self.day.contains(&Local::now().weekday().into()))
+    }
+}
+
+pub fn current_user() -> Option<String> {
+    let name = get_username_by_uid(unsafe { libc::getuid() })?;
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// Whether the user running `rust-motd` is a member of the group, as its primary group or not
+fn is_member_of(group: &str) -> bool {
+    let Ok(name) = CString::new(group) else {
+        return false;
+    };
+    let mut group = unsafe { std::mem::zeroed::<libc::group>() };
+    let mut buf = vec![0; 2048];
+    let mut result = std::ptr::null_mut::<libc::group>();
+    loop {
+        let r = unsafe {
+            libc::getgrnam_r(
+                name.as_ptr(),
+                &mut group,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+        if r != libc::ERANGE {
+            break;
+        }
+        let Some(newsize) = buf.len().checked_mul(2) else {
+            return false;
+        };
+        buf.resize(newsize, 0);
+    }
+    if result.is_null() {
+        // There is no such group
+        return false;
+    }
+    let gid = group.gr_gid;
+
+    let count = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+    let mut groups = vec![0; count.max(0) as usize];
+    let count = unsafe { libc::getgroups(count, groups.as_mut_ptr()) };
+    groups.truncate(count.max(0) as usize);
+    let (primary, effective) = unsafe { (libc::getgid(), libc::getegid()) };
+    primary == gid || effective == gid || groups.contains(&gid)
+}
+
+pub fn hostname() -> Option<String> {
+    let mut buf = vec![0; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr(), buf.len()) } != 0 {
+        return None;
+    }
+    let hostname = unsafe { CStr::from_ptr(buf.as_ptr()) };
+    Some(hostname.to_string_lossy().into_owned())
+}
+
+/// Match `text` against a pattern where `*` matches any characters and `?` matches one character
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    // Position of the last `*` in the pattern and of the text it was matched against,
+    // to backtrack to when the rest of the pattern does not match
+    let (mut p, mut t) = (0, 0);
+    let mut star = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("web-*", "web-01"));
+        assert!(glob_match("web-??", "web-01"));
+        assert!(glob_match("*.example.com", "db.example.com"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("web-?", "web-01"));
+        assert!(!glob_match("web-*", "db-01"));
+        assert!(!glob_match("a*b", "aXbY"));
+    }
+}
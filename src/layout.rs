@@ -0,0 +1,138 @@
+use std::io::{self, Write};
+use termion::style;
+use unicode_width::UnicodeWidthChar;
+
+/// A `row` node of the configuration, which prints its columns side by side
+#[derive(Debug)]
+pub struct RowLayout {
+    /// Number of spaces between the columns
+    pub gap: usize,
+    /// The configured width of each column, the columns without one share the remaining width
+    pub columns: Vec<Option<usize>>,
+}
+
+/// Where a component inside of a `row` is printed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    /// Index of the row in `Config::rows`
+    pub row: usize,
+    /// Index of the column in the row
+    pub column: usize,
+}
+
+impl RowLayout {
+    /// The width of each column, when the whole row is `width` columns wide
+    pub fn column_widths(&self, width: usize) -> Vec<usize> {
+        let gaps = self.gap * self.columns.len().saturating_sub(1);
+        let fixed: usize = self.columns.iter().flatten().sum();
+        let flexible = self
+            .columns
+            .iter()
+            .filter(|column| column.is_none())
+            .count();
+        let remaining = width.saturating_sub(gaps + fixed) / flexible.max(1);
+        self.columns
+            .iter()
+            .map(|column| column.unwrap_or(remaining))
+            .collect()
+    }
+
+    /// Print the output of the components of each column side by side
+    /// Lines that are too long for their column are truncated
+    pub fn print(
+        &self,
+        columns: &[Vec<u8>],
+        widths: &[usize],
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        let columns: Vec<Vec<String>> = columns
+            .iter()
+            .map(|column| {
+                String::from_utf8_lossy(column)
+                    .lines()
+                    .map(str::to_string)
+                    .collect()
+            })
+            .collect();
+        let height = columns.iter().map(Vec::len).max().unwrap_or(0);
+
+        for i in 0..height {
+            let line = columns
+                .iter()
+                .zip(widths)
+                .map(|(lines, &width)| fit_line(lines.get(i).map_or("", String::as_str), width))
+                .collect::<Vec<_>>()
+                .join(&" ".repeat(self.gap));
+            writeln!(out, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Truncate or pad `line` to exactly `width` columns
+/// Escape sequences (colours) do not take any space and are kept,
+/// and the style of a line with colours is reset at the end so that it does not leak into the next column
+fn fit_line(line: &str, width: usize) -> String {
+    let mut result = String::new();
+    let mut used = 0;
+    let mut styled = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Copy the whole control sequence (`ESC [ ... final byte`)
+            result.push(c);
+            styled = true;
+            if chars.peek() == Some(&'[') {
+                for c in chars.by_ref() {
+                    result.push(c);
+                    if ('@'..='~').contains(&c) && c != '[' {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+        let c_width = c.width().unwrap_or(0);
+        if used + c_width > width {
+            break;
+        }
+        result.push(c);
+        used += c_width;
+    }
+    if styled {
+        result += style::Reset.as_ref();
+    }
+    result + &" ".repeat(width - used)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_print_row() {
+        let row = RowLayout {
+            gap: 1,
+            columns: vec![Some(4), None, None],
+        };
+        let widths = row.column_widths(15);
+        assert_eq!(widths, vec![4, 4, 4]);
+
+        let columns = [
+            b"abcdef\nab\n".to_vec(),
+            "\x1b[31mred\x1b[m\n".as_bytes().to_vec(),
+            b"x\ny\nz\n".to_vec(),
+        ];
+        let mut out = vec![];
+        row.print(&columns, &widths, &mut out).unwrap();
+        let reset: &str = style::Reset.as_ref();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "abcd \x1b[31mred\x1b[m{reset}  x\n{}y\n{}z\n",
+                format_args!("ab{}", " ".repeat(8)),
+                " ".repeat(10)
+            )
+        );
+    }
+}
@@ -0,0 +1,26 @@
+//! Beautiful, useful MOTD generation
+//!
+//! The `rust-motd` binary is a thin wrapper around `cli::main`.
+//! Other binaries can add their own components to the built-in ones, by implementing
+//! `component::Component` and registering them in a `config::registry::Registry`:
+//! they can then be used in the KDL configuration like any other component.
+
+mod cache;
+mod check;
+pub mod cli;
+pub mod command;
+pub mod component;
+pub mod components;
+pub mod config;
+pub mod constants;
+pub mod layout;
+mod output;
+pub mod progress_bar;
+pub mod render;
+pub mod sysroot;
+pub mod timings;
+
+// Components are decoded with `knus` and implement `Component` with `async_trait`,
+// so other binaries can use the same versions as this crate
+pub use async_trait;
+pub use knus;
@@ -0,0 +1,133 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+
+/// Where we are in an ANSI escape sequence
+#[derive(Clone, Copy)]
+enum EscapeState {
+    /// Not in an escape sequence
+    Text,
+    /// Just read `ESC`
+    Escape,
+    /// In a control sequence (`ESC [`), which is terminated by a byte in `@`..=`~`
+    Csi,
+    /// In an operating system command (`ESC ]`), which is terminated by `BEL` or `ESC \`
+    Osc,
+    /// Read `ESC` inside of an operating system command
+    OscEscape,
+}
+
+/// Writer that removes ANSI escape sequences (colours, styles, etc.) from everything written to it
+/// The components always print their colours,
+/// and this is used when the output should not contain any (see `ColorMode`)
+/// This also removes colours from the output of external commands (like the `command` component)
+pub struct StripColors<W: Write> {
+    inner: W,
+    state: EscapeState,
+}
+
+impl<W: Write> StripColors<W> {
+    pub fn new(inner: W) -> Self {
+        StripColors {
+            inner,
+            state: EscapeState::Text,
+        }
+    }
+}
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+impl<W: Write> Write for StripColors<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut stripped = Vec::with_capacity(buf.len());
+
+        // The state is kept between calls because a sequence can be split across two writes
+        for &byte in buf {
+            self.state = match (self.state, byte) {
+                (EscapeState::Text, ESC) => EscapeState::Escape,
+                (EscapeState::Text, _) => {
+                    stripped.push(byte);
+                    EscapeState::Text
+                }
+                (EscapeState::Escape, b'[') => EscapeState::Csi,
+                (EscapeState::Escape, b']') => EscapeState::Osc,
+                // Other escape sequences are only two bytes long
+                (EscapeState::Escape, _) => EscapeState::Text,
+                (EscapeState::Csi, b'@'..=b'~') => EscapeState::Text,
+                (EscapeState::Csi, _) => EscapeState::Csi,
+                (EscapeState::Osc, BEL) => EscapeState::Text,
+                (EscapeState::Osc, ESC) => EscapeState::OscEscape,
+                (EscapeState::Osc, _) => EscapeState::Osc,
+                (EscapeState::OscEscape, b'\\') => EscapeState::Text,
+                (EscapeState::OscEscape, _) => EscapeState::Osc,
+            };
+        }
+
+        self.inner.write_all(&stripped)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Wrap `inner` in `StripColors` unless colours should be printed
+pub fn with_colors<'a>(inner: impl Write + 'a, colors: bool) -> Box<dyn Write + 'a> {
+    if colors {
+        Box::new(inner)
+    } else {
+        Box::new(StripColors::new(inner))
+    }
+}
+
+/// Replace the contents of `path` with `contents` atomically
+/// The data is written to a temporary file in the same directory, which is then renamed over
+/// `path`, so that readers (like `pam_motd`) never see a partially written file
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "the output path is not a file")
+    })?;
+    let mut temp_name = OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".{}.tmp", process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use termion::{color, style};
+
+    #[test]
+    fn test_strip_colors() {
+        let mut out = StripColors::new(Vec::new());
+        write!(
+            out,
+            "{}red{} [{}===={}====]",
+            color::Fg(color::Red),
+            style::Reset,
+            color::Fg(color::Rgb(1, 2, 3)),
+            color::Fg(color::LightBlack)
+        )
+        .unwrap();
+        // Split in the middle of an escape sequence
+        out.write_all(b"\x1b[3").unwrap();
+        out.write_all(b"8;5;1mdone\x1b]0;title\x07").unwrap();
+        assert_eq!(String::from_utf8(out.inner).unwrap(), "red [========]done");
+    }
+}
@@ -0,0 +1,208 @@
+use termion::style;
+use unicode_width::UnicodeWidthStr;
+
+use crate::config::global_config::{GlobalConfig, ProgressLabel};
+use crate::config::theme::Level;
+
+/// Partially filled cells with `progress-smooth`, from 1/8 to 7/8 of a cell
+const EIGHTHS: [&str; 7] = ["▏", "▎", "▍", "▌", "▋", "▊", "▉"];
+const FULL_BLOCK: &str = "█";
+/// Width of a label printed beside the bar (like ` 100%`)
+const BESIDE_LABEL_WIDTH: usize = 5;
+
+/// Part of a bar taking `width` columns, printed in `color` (or the default colour if `None`)
+struct Cell {
+    text: String,
+    width: usize,
+    color: Option<String>,
+}
+
+impl Cell {
+    fn new(text: &str, color: Option<String>) -> Self {
+        Cell {
+            text: text.to_string(),
+            width: text.width(),
+            color,
+        }
+    }
+}
+
+/// A progress bar showing a ratio (like the used space of a filesystem)
+/// It is coloured according to the warn and critical thresholds (in percent),
+/// and styled with the `progress-*` settings of the global configuration
+/// This is shared by all the components printing bars so that they look the same
+pub struct ProgressBar {
+    pub ratio: f64,
+    pub warn: usize,
+    pub critical: usize,
+}
+
+impl ProgressBar {
+    /// Render the bar in `width` columns, including the prefix, the suffix and the label
+    /// Ratios outside of [0, 1] are clamped, and NaN (e.g. the usage of an empty filesystem) is 0
+    /// The filled part is rounded down, so that a bar is only full at 100%
+    pub fn render(&self, global_config: &GlobalConfig, width: usize) -> String {
+        let ratio = if self.ratio.is_nan() {
+            0.0
+        } else {
+            self.ratio.clamp(0.0, 1.0)
+        };
+        let prefix = &global_config.progress_prefix;
+        let suffix = &global_config.progress_suffix;
+        let label_width = match global_config.progress_label {
+            ProgressLabel::Beside => BESIDE_LABEL_WIDTH,
+            ProgressLabel::None | ProgressLabel::Inside => 0,
+        };
+        let inner_width = width.saturating_sub(prefix.width() + suffix.width() + label_width);
+
+        let mut cells = self.cells(global_config, ratio, inner_width);
+        let percent = format!("{:.0}%", ratio * 100.0);
+        if let ProgressLabel::Inside = global_config.progress_label {
+            if percent.len() <= inner_width {
+                cells = overlay(cells, (inner_width - percent.len()) / 2, &percent);
+            }
+        }
+
+        let mut bar = prefix.to_string();
+        let mut current_color = None;
+        for cell in cells {
+            if cell.color != current_color {
+                match &cell.color {
+                    Some(color) => bar += color,
+                    None => bar += style::Reset.as_ref(),
+                }
+                current_color = cell.color;
+            }
+            bar += &cell.text;
+        }
+        bar += style::Reset.as_ref();
+        bar += suffix;
+        if let ProgressLabel::Beside = global_config.progress_label {
+            bar += &format!("{percent:>width$}", width = BESIDE_LABEL_WIDTH);
+        }
+        bar
+    }
+
+    /// The colour of the filled part of the bar up to `position` (a ratio of the whole bar)
+    /// With `progress-gradient`, each part of the bar has the colour of its own position,
+    /// otherwise the whole bar has the colour of the ratio
+    fn full_color(&self, global_config: &GlobalConfig, ratio: f64, position: f64) -> String {
+        let ratio = if global_config.progress_gradient {
+            position
+        } else {
+            ratio
+        };
+        global_config
+            .theme
+            .level(Level::from_usage(ratio, self.warn, self.critical))
+    }
+
+    fn cells(&self, global_config: &GlobalConfig, ratio: f64, width: usize) -> Vec<Cell> {
+        let mut cells = vec![];
+        let position = |column: usize| column as f64 / width as f64;
+
+        if global_config.progress_smooth {
+            let eighths = (ratio * width as f64 * 8.0) as usize;
+            for column in 0..eighths / 8 {
+                let color = self.full_color(global_config, ratio, position(column + 1));
+                cells.push(Cell::new(FULL_BLOCK, Some(color)));
+            }
+            let partial = eighths % 8;
+            if partial > 0 {
+                let color = self.full_color(global_config, ratio, position(eighths / 8 + 1));
+                cells.push(Cell::new(EIGHTHS[partial - 1], Some(color)));
+            }
+        } else {
+            // Characters may be wider than one column (or have a width of zero)
+            let full_character = &global_config.progress_full_character;
+            let full_width = full_character.width().max(1);
+            let full_columns = (ratio * width as f64) as usize;
+            for column in (0..full_columns / full_width).map(|i| (i + 1) * full_width) {
+                let color = self.full_color(global_config, ratio, position(column));
+                cells.push(Cell::new(full_character, Some(color)));
+            }
+        }
+
+        let used_width: usize = cells.iter().map(|cell| cell.width).sum();
+        let empty_character = &global_config.progress_empty_character;
+        let empty_width = empty_character.width().max(1);
+        let empty_count = (width - used_width) / empty_width;
+        for _ in 0..empty_count {
+            cells.push(Cell::new(
+                empty_character,
+                Some(global_config.theme.muted()),
+            ));
+        }
+        // Pad if the characters do not fill the width exactly
+        for _ in used_width + empty_count * empty_width..width {
+            cells.push(Cell::new(" ", None));
+        }
+        cells
+    }
+}
+
+/// Replace the cells from column `start` with `label`
+/// The columns of wide characters that are only partially covered by the label become spaces
+fn overlay(cells: Vec<Cell>, start: usize, label: &str) -> Vec<Cell> {
+    let end = start + label.width();
+    let mut result = vec![];
+    let mut column = 0;
+    for cell in cells {
+        let (cell_start, cell_end) = (column, column + cell.width);
+        column = cell_end;
+        if cell_end <= start || cell_start >= end {
+            result.push(cell);
+            continue;
+        }
+        for column in cell_start..cell_end {
+            if column == start {
+                result.push(Cell::new(label, None));
+            } else if column < start || column >= end {
+                result.push(Cell::new(" ", cell.color.clone()));
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(bar: &str) -> String {
+        let mut out = vec![];
+        let mut writer = crate::output::StripColors::new(&mut out);
+        std::io::Write::write_all(&mut writer, bar.as_bytes()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_render() {
+        let mut global_config = GlobalConfig::default();
+        let bar = |ratio: f64, global_config: &GlobalConfig| {
+            let bar = ProgressBar {
+                ratio,
+                warn: 75,
+                critical: 95,
+            };
+            strip(&bar.render(global_config, 12))
+        };
+
+        assert_eq!(bar(0.5, &global_config), "[==========]");
+        assert_eq!(bar(f64::NAN, &global_config), "[==========]");
+        assert_eq!(bar(2.0, &global_config), "[==========]");
+
+        global_config.progress_full_character = "█".to_string();
+        global_config.progress_empty_character = "·".to_string();
+        assert_eq!(bar(0.3, &global_config), "[███·······]");
+
+        global_config.progress_smooth = true;
+        assert_eq!(bar(0.25, &global_config), "[██▌·······]");
+        assert_eq!(bar(-1.0, &global_config), "[··········]");
+
+        global_config.progress_label = ProgressLabel::Inside;
+        assert_eq!(bar(0.25, &global_config), "[██▌25%····]");
+        global_config.progress_label = ProgressLabel::Beside;
+        assert_eq!(bar(0.25, &global_config), "[█▎···]  25%");
+    }
+}
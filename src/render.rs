@@ -0,0 +1,650 @@
+use chrono::Local;
+use clap::ValueEnum;
+use miette::GraphicalReportHandler;
+use serde_json::json;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use termion::style;
+use tokio::runtime::Handle;
+use tokio::task::{JoinError, JoinHandle};
+use tokio::time::Instant;
+
+use crate::cache::{cache_key, Cache, CachedCollected};
+use crate::component::{
+    BoxedCollected, BoxedComponent, CollectedError, ComponentError, ComponentOptions,
+    ConfiguredComponent, FailedComponent, PerfData, PrepareReturn, Selection, Severity,
+};
+use crate::config::get_config::get_config;
+use crate::config::global_config::{ErrorPolicy, GlobalConfig, ProgressWidth};
+use crate::config::registry::Registry;
+use crate::config::theme::Level;
+use crate::constants::INDENT_WIDTH;
+use crate::layout::{Placement, RowLayout};
+use crate::output::{with_colors, write_atomically};
+use crate::sysroot::SysRoot;
+use crate::timings::{write_timings, Timing, TimingsFormat};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum Format {
+    /// Coloured text, meant to be read by humans
+    #[default]
+    Text,
+    /// A JSON array with the data collected by each component, meant for scripts and dashboards
+    Json,
+    /// The one-line summary of `rust-motd status`
+    #[value(skip)]
+    Status,
+}
+
+/// The width of the terminal, if the MOTD is printed to one
+fn terminal_width(to_stdout: bool) -> Option<usize> {
+    if !to_stdout || !io::stdout().is_terminal() {
+        return None;
+    }
+    termion::terminal_size()
+        .ok()
+        .map(|(columns, _)| columns as usize)
+}
+
+/// A component that is ready to be collected, or whose output was found in the cache
+struct PreparedComponent {
+    component: BoxedComponent,
+    options: ComponentOptions,
+    position: usize,
+    placement: Option<Placement>,
+    /// The time it took to prepare the component, nothing if it was found in the cache instead
+    /// (then it is only prepared if the cached output does not have the width of this run)
+    prepare: Option<Duration>,
+    /// The minimum width returned by the prepare phase, or stored in the cache
+    min_width: Option<usize>,
+    cached: Option<CachedCollected>,
+    /// Identifies the output of the component in the cache
+    key: u64,
+}
+
+/// How long a component that timed out has to stop collecting (like killing its commands),
+/// before it is no longer waited for
+const CANCEL_GRACE: Duration = Duration::from_millis(100);
+
+/// A component that is being collected, or whose output is already known (from the cache)
+/// along with how long it took to collect (nothing for the cache)
+enum Pending {
+    Ready(BoxedCollected, Option<Duration>),
+    Running(JoinHandle<(BoxedCollected, Duration)>, Option<u64>),
+}
+
+impl Pending {
+    /// Wait for the data of a component, giving up once its timeout has passed since `start`
+    /// Also returns how long the component took to collect, if it was collected
+    async fn wait(
+        self,
+        name: &'static str,
+        start: Instant,
+    ) -> Result<(BoxedCollected, Option<Duration>), JoinError> {
+        let (handle, timeout) = match self {
+            Pending::Ready(collected, elapsed) => return Ok((collected, elapsed)),
+            Pending::Running(handle, timeout) => (handle, timeout),
+        };
+        // The collect phase gives up on its own at the same time, if it is waiting asynchronously
+        // (like for a command, which is then killed), so it is given a moment to do so
+        Ok(
+            match wait_until(handle, name, start + CANCEL_GRACE, timeout).await? {
+                Ok((collected, elapsed)) => (collected, Some(elapsed)),
+                Err(err) => (Box::new(CollectedError(err)), Some(start.elapsed())),
+            },
+        )
+    }
+}
+
+/// Wait for a task of a component (preparing or collecting it),
+/// giving up once its timeout has passed since `start`
+async fn wait_until<T>(
+    handle: JoinHandle<T>,
+    name: &'static str,
+    start: Instant,
+    timeout: Option<u64>,
+) -> Result<Result<T, ComponentError>, JoinError> {
+    let Some(timeout) = timeout else {
+        return handle.await.map(Ok);
+    };
+    match tokio::time::timeout_at(start + Duration::from_secs(timeout), handle).await {
+        Ok(result) => result.map(Ok),
+        Err(_) => Ok(Err(timed_out(name, timeout))),
+    }
+}
+
+fn timed_out(name: &'static str, timeout: u64) -> ComponentError {
+    ComponentError::new(name, format!("timed out after {timeout}s"))
+}
+
+/// Prepare a component on its own blocking thread, like its collect phase,
+/// since some components already read the system when preparing (like `filesystems`)
+/// Also returns how long the component took to prepare
+fn spawn_prepare(
+    component: BoxedComponent,
+    global_config: &Arc<GlobalConfig>,
+) -> JoinHandle<(PrepareReturn, Duration)> {
+    let global_config = global_config.clone();
+    tokio::task::spawn_blocking(move || {
+        let prepare_start = Instant::now();
+        let prepared = component.prepare(&global_config);
+        (prepared, prepare_start.elapsed())
+    })
+}
+
+/// Print the summary of `rust-motd status`, like `MOTD CRITICAL - filesystems CRITICAL | ...`
+/// The exit code is the one of the worst severity
+fn print_status(
+    out: &mut dyn Write,
+    statuses: &[(&str, Severity, Vec<PerfData>)],
+) -> io::Result<ExitCode> {
+    let worst = statuses
+        .iter()
+        .map(|(_, severity, _)| *severity)
+        .max()
+        .unwrap_or_default();
+    let summary = match worst {
+        Severity::Ok => format!("all {} components OK", statuses.len()),
+        _ => statuses
+            .iter()
+            .filter(|(_, severity, _)| *severity != Severity::Ok)
+            .map(|(name, severity, _)| format!("{name} {}", severity.plugin_state()))
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+    let perfdata = statuses
+        .iter()
+        .flat_map(|(_, _, perfdata)| perfdata)
+        .map(PerfData::to_string)
+        .collect::<Vec<_>>();
+    write!(out, "MOTD {} - {summary}", worst.plugin_state())?;
+    if !perfdata.is_empty() {
+        write!(out, " | {}", perfdata.join(" "))?;
+    }
+    writeln!(out)?;
+    Ok(ExitCode::from(worst.exit_code()))
+}
+
+/// How to run the components and print the MOTD, as given on the command line
+#[derive(Debug, Default, Clone)]
+pub struct RunOptions {
+    /// The components to run (`--only` and `--skip`)
+    pub selection: Selection,
+    pub format: Format,
+    /// Write the MOTD to this file instead of printing it to stdout
+    pub output: Option<PathBuf>,
+    /// Print the details of the errors of the components to stderr
+    pub verbose: bool,
+    /// Report the time spent by each component to stderr at the end
+    pub timings: Option<TimingsFormat>,
+}
+
+/// Print the MOTD to stdout, or write it to the `output` of the options if given
+/// The components of the registry can be used in the configuration
+pub async fn run(
+    config_path: Option<String>,
+    registry: &Registry,
+    sysroot: SysRoot,
+    options: &RunOptions,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let run_start = Instant::now();
+    let mut config = match get_config(config_path, registry, &sysroot) {
+        Ok(config) => config,
+        // A monitoring system only shows the first line, and must know that nothing was checked
+        Err(e) if matches!(options.format, Format::Status) => {
+            println!("MOTD UNKNOWN - Config Error: {e}");
+            return Ok(ExitCode::from(Severity::Unknown.exit_code()));
+        }
+        Err(e) => {
+            println!("Config Error: {:?}", miette::Report::new(e));
+            return Ok(ExitCode::SUCCESS);
+        }
+    };
+    config.global.sysroot = sysroot;
+    options.selection.apply(&mut config.components);
+    let global_config = Arc::new(config.global);
+    let cache = Cache::new(&global_config);
+    // The timeout of each component covers both its prepare and collect phases
+    let start = Instant::now();
+
+    let components = prepare(config.components, &global_config, cache.as_ref(), start).await?;
+    let widths = Widths::new(
+        &components,
+        &global_config,
+        config.rows,
+        options.output.is_none(),
+    );
+    let (collecting, mut component_timings) =
+        collect(components, &global_config, cache.as_ref(), &widths, start).await?;
+    let exit_code = print(
+        collecting,
+        &global_config,
+        &widths,
+        options,
+        &mut component_timings,
+        start,
+    )
+    .await?;
+
+    if let Some(timings) = options.timings {
+        write_timings(
+            &mut io::stderr(),
+            timings,
+            component_timings,
+            run_start.elapsed(),
+        )?;
+    }
+    Ok(exit_code)
+}
+
+/// Run the prepare phase of each component
+/// Allow each component to specify its sizing constraints (like min width)
+/// Components whose output is still in the cache (with a `cache-ttl`) are not prepared,
+/// since some of them do most of their work in the prepare phase
+/// The components are prepared concurrently, and give up once their timeout has passed
+async fn prepare(
+    components: Vec<ConfiguredComponent>,
+    global_config: &Arc<GlobalConfig>,
+    cache: Option<&Cache>,
+    start: Instant,
+) -> Result<Vec<PreparedComponent>, JoinError> {
+    let mut preparing = vec![];
+    // Components whose `when` conditions do not match cost nothing
+    for ConfiguredComponent {
+        component,
+        options,
+        kdl,
+        position,
+        placement,
+    } in components
+        .into_iter()
+        .filter(|component| component.options.is_enabled())
+    {
+        let key = cache_key(&kdl, global_config);
+        let cached = cache
+            .zip(options.cache_ttl)
+            .and_then(|(cache, ttl)| cache.load(component.name(), key, ttl));
+        let name = component.name();
+        let prepared = match cached {
+            Some(cached) => Err((component, cached)),
+            None => Ok(spawn_prepare(component, global_config)),
+        };
+        preparing.push((name, prepared, options, position, placement, key));
+    }
+
+    let mut components = vec![];
+    for (name, prepared, options, position, placement, key) in preparing {
+        let timeout = options.timeout.or(global_config.timeout);
+        let (component, prepare, min_width, cached) = match prepared {
+            Err((component, cached)) => (component, None, cached.min_width, Some(cached)),
+            Ok(handle) => match wait_until(handle, name, start, timeout).await? {
+                Ok((None, _)) => continue,
+                Ok((Some((component, constraints)), elapsed)) => (
+                    component,
+                    Some(elapsed),
+                    constraints.and_then(|constraints| constraints.min_width),
+                    None,
+                ),
+                Err(err) => (
+                    Box::new(FailedComponent(err)) as BoxedComponent,
+                    Some(start.elapsed()),
+                    None,
+                    None,
+                ),
+            },
+        };
+        components.push(PreparedComponent {
+            component,
+            options,
+            position,
+            placement,
+            prepare,
+            min_width,
+            cached,
+            key,
+        });
+    }
+    Ok(components)
+}
+
+/// The widths the components are printed with
+struct Widths {
+    /// The width of the components that are not inside of a row
+    width: usize,
+    /// The rows of the configuration, with the widths of their columns
+    rows: Vec<(RowLayout, Vec<usize>)>,
+}
+
+impl Widths {
+    /// The width to use is the maximum of all the component's minimum widths
+    /// (or the configured width), but no more than the width of the terminal
+    /// With `progress-width "auto"`, the components fill the width of the terminal
+    /// Components that are wider than this shrink or truncate their content
+    /// Rows take the whole width of the terminal (including the indentation),
+    /// which is shared by their columns
+    fn new(
+        components: &[PreparedComponent],
+        global_config: &GlobalConfig,
+        rows: Vec<RowLayout>,
+        to_stdout: bool,
+    ) -> Self {
+        let terminal_width = terminal_width(to_stdout);
+        let full_width = terminal_width.unwrap_or(ProgressWidth::Auto.fallback());
+        let min_width = components
+            .iter()
+            .filter_map(|component| component.min_width)
+            .max();
+        let width = match global_config.progress_width {
+            ProgressWidth::Auto => full_width.saturating_sub(INDENT_WIDTH),
+            ProgressWidth::Fixed(width) => {
+                let width = min_width.unwrap_or(width.saturating_sub(INDENT_WIDTH));
+                terminal_width.map_or(width, |terminal_width| {
+                    width.min(terminal_width.saturating_sub(INDENT_WIDTH))
+                })
+            }
+        };
+        let rows = rows
+            .into_iter()
+            .map(|row| {
+                let widths = row.column_widths(full_width);
+                (row, widths)
+            })
+            .collect();
+        Widths { width, rows }
+    }
+
+    /// Components inside of a row are printed with the width of their column
+    fn component(&self, placement: Option<Placement>) -> Option<usize> {
+        match placement {
+            Some(Placement { row, column }) => {
+                Some(self.rows[row].1[column].saturating_sub(INDENT_WIDTH))
+            }
+            None => Some(self.width),
+        }
+    }
+}
+
+/// A component whose data is being collected, or is already known
+struct CollectingComponent<'a> {
+    name: &'static str,
+    pending: Pending,
+    placement: Option<Placement>,
+    /// Where to store the output of the component, if it has a `cache-ttl`,
+    /// with its key and minimum width
+    cache_entry: Option<(&'a Cache, u64, Option<usize>)>,
+}
+
+/// Collect the data for all components concurrently
+/// Each component gets its own blocking thread, because most of them
+/// run commands or read files synchronously
+/// Components whose output is in the cache are not collected at all
+/// Also returns the timings of the components, whose collect and print times are set later
+async fn collect<'a>(
+    components: Vec<PreparedComponent>,
+    global_config: &Arc<GlobalConfig>,
+    cache: Option<&'a Cache>,
+    widths: &Widths,
+    start: Instant,
+) -> Result<(Vec<CollectingComponent<'a>>, Vec<Timing>), JoinError> {
+    let has_summary = components
+        .iter()
+        .any(|prepared| prepared.component.name() == "summary");
+    let mut component_timings = vec![];
+    let mut collecting = vec![];
+    for PreparedComponent {
+        component,
+        options,
+        position,
+        placement,
+        prepare,
+        min_width,
+        cached,
+        key,
+    } in components
+    {
+        let name = component.name();
+        let mut timing = |prepare| {
+            component_timings.push(Timing {
+                position,
+                component: name,
+                prepare,
+                ..Default::default()
+            })
+        };
+        // The cached text is only used if it was printed with the same width,
+        // otherwise the component is prepared after all
+        let width = widths.component(placement);
+        let timeout = options.timeout.or(global_config.timeout);
+        let component = match (cached, prepare) {
+            (Some(cached), _) if cached.width == width => {
+                timing(Duration::ZERO);
+                collecting.push(CollectingComponent {
+                    name,
+                    pending: Pending::Ready(Box::new(cached), None),
+                    placement,
+                    cache_entry: None,
+                });
+                continue;
+            }
+            (_, Some(prepare)) => {
+                timing(prepare);
+                component
+            }
+            (_, None) => {
+                let handle = spawn_prepare(component, global_config);
+                match wait_until(handle, name, start, timeout).await? {
+                    Ok((None, _)) => continue,
+                    Ok((Some((component, _)), elapsed)) => {
+                        timing(elapsed);
+                        component
+                    }
+                    Err(err) => {
+                        timing(start.elapsed());
+                        Box::new(FailedComponent(err))
+                    }
+                }
+            }
+        };
+
+        let cache_entry = cache
+            .filter(|_| options.cache_ttl.is_some())
+            .map(|cache| (cache, key, min_width));
+        // The collect future is dropped once the timeout has passed,
+        // which kills the commands it is still waiting for
+        let deadline = timeout.map(|timeout| (start + Duration::from_secs(timeout), timeout));
+        let global_config = global_config.clone();
+        let handle = tokio::task::spawn_blocking(move || {
+            let collect_start = Instant::now();
+            let collect = component.collect(&global_config);
+            let collected = match deadline {
+                Some((deadline, timeout)) => Handle::current()
+                    .block_on(tokio::time::timeout_at(deadline, collect))
+                    .unwrap_or_else(|_| Box::new(CollectedError(timed_out(name, timeout)))),
+                None => Handle::current().block_on(collect),
+            };
+            (collected, collect_start.elapsed())
+        });
+        collecting.push(CollectingComponent {
+            name,
+            pending: Pending::Running(handle, timeout),
+            placement,
+            cache_entry,
+        });
+    }
+
+    // The summary needs the issues of all the components, so they are all collected
+    // before anything is printed
+    if has_summary {
+        let mut collected = vec![];
+        for CollectingComponent {
+            name,
+            pending,
+            placement,
+            cache_entry,
+        } in collecting
+        {
+            let (data, elapsed) = pending.wait(name, start).await?;
+            collected.push((name, data, elapsed, placement, cache_entry));
+        }
+        let issues: Vec<_> = collected
+            .iter()
+            .flat_map(|(_, data, _, _, _)| data.issues())
+            .collect();
+        collecting = collected
+            .into_iter()
+            .map(|(name, mut data, elapsed, placement, cache_entry)| {
+                data.summarize(&issues);
+                CollectingComponent {
+                    name,
+                    pending: Pending::Ready(data, elapsed),
+                    placement,
+                    cache_entry,
+                }
+            })
+            .collect();
+    }
+    Ok((collecting, component_timings))
+}
+
+/// Print each component with its width, in the order of the configuration file,
+/// waiting for the data of each component in turn
+/// Returns the exit code, which is the one of the worst severity for `rust-motd status`
+async fn print(
+    collecting: Vec<CollectingComponent<'_>>,
+    global_config: &GlobalConfig,
+    widths: &Widths,
+    options: &RunOptions,
+    component_timings: &mut [Timing],
+    start: Instant,
+) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let mut exit_code = ExitCode::SUCCESS;
+    // A rendered file is meant to be shown on a terminal later,
+    // so it is treated like a terminal when deciding whether to print colours
+    let stdout = io::stdout();
+    let mut rendered = vec![];
+    let mut out = match options.output {
+        Some(_) => with_colors(&mut rendered, global_config.color.enabled(true)),
+        None => with_colors(
+            stdout.lock(),
+            global_config.color.enabled(stdout.is_terminal()),
+        ),
+    };
+    // The output of the components inside of a row is buffered per column,
+    // and the row is printed once all of its components are done
+    let mut json_components = vec![];
+    let mut statuses = vec![];
+    let mut footer = vec![];
+    let mut current_row: Option<(usize, Vec<Vec<u8>>)> = None;
+    for (
+        timing,
+        CollectingComponent {
+            name,
+            pending,
+            placement,
+            cache_entry,
+        },
+    ) in component_timings.iter_mut().zip(collecting)
+    {
+        let width = widths.component(placement);
+        let (mut collected, elapsed) = pending.wait(name, start).await?;
+        let print_start = Instant::now();
+        timing.collect = elapsed;
+        timing.cached = elapsed.is_none();
+        timing.error = collected.error().is_some();
+        if let Some(error) = collected.error() {
+            if options.verbose {
+                let mut report = String::new();
+                GraphicalReportHandler::new().render_report(&mut report, error)?;
+                eprint!("{report}");
+            }
+            if global_config.on_error == ErrorPolicy::Footer {
+                footer.push(error.message());
+            }
+        }
+        if let Some((cache, key, min_width)) = cache_entry.filter(|_| collected.error().is_none()) {
+            let data = collected.to_json();
+            let (issues, perfdata) = (collected.issues(), collected.perfdata());
+            let mut text = vec![];
+            collected.print(global_config, width, &mut text)?;
+            let cached = CachedCollected {
+                text: String::from_utf8_lossy(&text).into_owned(),
+                data,
+                issues,
+                perfdata,
+                min_width,
+                width,
+            };
+            if let Err(err) = cache.store(name, key, &cached) {
+                eprintln!("Could not cache the output of {name}: {err}");
+            }
+            collected = Box::new(cached);
+        }
+        if placement.map(|placement| placement.row) != current_row.as_ref().map(|row| row.0) {
+            if let Some((row, columns)) = current_row.take() {
+                let (layout, column_widths) = &widths.rows[row];
+                layout.print(&columns, column_widths, &mut out)?;
+            }
+            if let Some(placement) = placement {
+                let columns = vec![vec![]; widths.rows[placement.row].1.len()];
+                current_row = Some((placement.row, columns));
+            }
+        }
+        match options.format {
+            Format::Text => match (placement, &mut current_row) {
+                (Some(placement), Some((_, columns))) => {
+                    collected.print(global_config, width, &mut columns[placement.column])?
+                }
+                _ => collected.print(global_config, width, &mut out)?,
+            },
+            Format::Json => json_components.push(json!({
+                "component": name,
+                "severity": collected.severity(),
+                "data": collected.to_json(),
+            })),
+            Format::Status => statuses.push((name, collected.severity(), collected.perfdata())),
+        }
+        timing.print = print_start.elapsed();
+    }
+    if let Some((row, columns)) = current_row {
+        let (layout, column_widths) = &widths.rows[row];
+        layout.print(&columns, column_widths, &mut out)?;
+    }
+    match options.format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut out, &json_components)?;
+            writeln!(out)?;
+        }
+        Format::Status => exit_code = print_status(&mut out, &statuses)?,
+        Format::Text if !footer.is_empty() => {
+            writeln!(
+                out,
+                "{}Errors:{}",
+                global_config.theme.level(Level::Critical),
+                style::Reset
+            )?;
+            for message in footer {
+                writeln!(out, "{}{message}", " ".repeat(INDENT_WIDTH))?;
+            }
+            writeln!(out)?;
+        }
+        Format::Text => (),
+    }
+    // Tell the reader how old a rendered file is
+    if let (Some(_), Format::Text) = (&options.output, options.format) {
+        writeln!(
+            out,
+            "Generated at {}",
+            Local::now().format(&global_config.time_format)
+        )?;
+    }
+    out.flush()?;
+    drop(out);
+
+    if let Some(output) = &options.output {
+        write_atomically(output, &rendered)?;
+    }
+    Ok(exit_code)
+}
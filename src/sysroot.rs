@@ -0,0 +1,287 @@
+use libc::passwd as c_passwd;
+use libc::uid_t;
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::thread::available_parallelism;
+use std::time::Duration;
+use systemstat::{ByteSize, Filesystem, LoadAverage, Platform, System};
+
+/// Where the components read the state of the system from (`/proc`, `/sys`, `/var/log`...)
+/// This is `/` by default, but it can be set to another directory with `--sysroot`,
+/// like the root of the host mounted in a container, or a directory of fixtures in the tests
+/// The live system (`/`) is queried the usual way (system calls, NSS),
+/// while any other root is only read from its files
+#[derive(Debug, Clone)]
+pub struct SysRoot {
+    root: PathBuf,
+    statvfs: Statvfs,
+}
+
+/// Read the usage of the filesystem mounted at a path (inside of the root)
+pub type Statvfs = fn(&Path) -> io::Result<MountUsage>;
+
+impl Default for SysRoot {
+    fn default() -> Self {
+        SysRoot::new("/")
+    }
+}
+
+impl SysRoot {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        SysRoot {
+            root: root.into(),
+            statvfs,
+        }
+    }
+
+    /// Read the usage of the filesystems of a root that is not live with `statvfs`
+    /// instead of the system call, like fixed usages for the mount points of fixtures
+    pub fn with_statvfs(self, statvfs: Statvfs) -> Self {
+        SysRoot { statvfs, ..self }
+    }
+
+    /// Whether this is the root of the system `rust-motd` runs on
+    pub fn is_live(&self) -> bool {
+        self.root == Path::new("/")
+    }
+
+    /// The path of a file of the system (like `/proc/meminfo`) inside of this root
+    /// Relative paths are left as is
+    pub fn path(&self, path: impl AsRef<Path>) -> PathBuf {
+        let path = path.as_ref();
+        match path.strip_prefix("/") {
+            Ok(relative) => self.root.join(relative),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+
+    /// Read a file of the system, the error says which file could not be read
+    pub fn read_to_string(&self, path: impl AsRef<Path>) -> io::Result<String> {
+        let path = self.path(path);
+        fs::read_to_string(&path)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())))
+    }
+
+    /// The quantities of `/proc/meminfo`, like `MemTotal`
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn meminfo(&self) -> io::Result<BTreeMap<String, ByteSize>> {
+        if self.is_live() {
+            return Ok(System::new().memory()?.platform_memory.meminfo);
+        }
+        Ok(self
+            .read_to_string("/proc/meminfo")?
+            .lines()
+            .filter_map(|line| {
+                let (key, value) = line.split_once(':')?;
+                let kib = value.trim().strip_suffix("kB")?.trim().parse().ok()?;
+                Some((key.to_string(), ByteSize::kib(kib)))
+            })
+            .collect())
+    }
+
+    pub fn load_average(&self) -> io::Result<LoadAverage> {
+        if self.is_live() {
+            return System::new().load_average();
+        }
+        let loadavg = self.read_to_string("/proc/loadavg")?;
+        let mut values = loadavg.split_whitespace().map(str::parse::<f32>);
+        match (values.next(), values.next(), values.next()) {
+            (Some(Ok(one)), Some(Ok(five)), Some(Ok(fifteen))) => {
+                Ok(LoadAverage { one, five, fifteen })
+            }
+            _ => Err(invalid_data("/proc/loadavg")),
+        }
+    }
+
+    pub fn uptime(&self) -> io::Result<Duration> {
+        if self.is_live() {
+            return System::new().uptime();
+        }
+        self.read_to_string("/proc/uptime")?
+            .split_whitespace()
+            .next()
+            .and_then(|seconds| seconds.parse::<f64>().ok())
+            .map(|seconds| Duration::from_secs(seconds as u64))
+            .ok_or_else(|| invalid_data("/proc/uptime"))
+    }
+
+    /// The mounted filesystems of `/proc/mounts` with their usage,
+    /// the ones whose usage cannot be read (like mount points that are not accessible) are skipped
+    pub fn mounts(&self) -> io::Result<Vec<Filesystem>> {
+        if self.is_live() {
+            return System::new().mounts();
+        }
+        Ok(self
+            .read_to_string("/proc/mounts")?
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let (source, target, fs_type) = (fields.next()?, fields.next()?, fields.next()?);
+                let usage = (self.statvfs)(&self.path(target)).ok()?;
+                Some(usage.filesystem(source, target, fs_type))
+            })
+            .collect())
+    }
+
+    /// The number of CPUs, to put loads in proportion
+    /// On the live system, these are the CPUs available to `rust-motd` (which respects quotas),
+    /// otherwise the CPUs that are online, like `0-3,6`
+    pub fn cpus(&self) -> io::Result<usize> {
+        if self.is_live() {
+            return Ok(available_parallelism()?.get());
+        }
+        let path = "/sys/devices/system/cpu/online";
+        let mut cpus = 0;
+        for range in self.read_to_string(path)?.trim().split(',') {
+            let (first, last) = range.split_once('-').unwrap_or((range, range));
+            match (first.parse::<usize>(), last.parse::<usize>()) {
+                (Ok(first), Ok(last)) if first <= last => cpus += last - first + 1,
+                _ => return Err(invalid_data(path)),
+            }
+        }
+        Ok(cpus)
+    }
+
+    /// The name of the user with the given UID,
+    /// from the user database on the live system, otherwise from `/etc/passwd`
+    pub fn user_name(&self, uid: uid_t) -> Option<String> {
+        if self.is_live() {
+            return get_username_by_uid(uid).map(|name| name.to_string_lossy().into_owned());
+        }
+        self.read_to_string("/etc/passwd")
+            .ok()?
+            .lines()
+            .find_map(|line| {
+                let mut fields = line.split(':');
+                let name = fields.next()?;
+                let user_uid = fields.nth(1)?.parse::<uid_t>().ok()?;
+                (user_uid == uid).then(|| name.to_string())
+            })
+    }
+}
+
+/// The fields of `statvfs` that make up the usage of a filesystem
+#[derive(Debug, Clone, Copy)]
+pub struct MountUsage {
+    pub block_size: u64,
+    pub blocks: u64,
+    pub blocks_free: u64,
+    pub blocks_avail: u64,
+    pub files: u64,
+    pub files_free: u64,
+}
+
+impl MountUsage {
+    fn filesystem(self, source: &str, target: &str, fs_type: &str) -> Filesystem {
+        let bytes = |blocks| ByteSize::b(blocks * self.block_size);
+        Filesystem {
+            files: self.files.saturating_sub(self.files_free) as usize,
+            files_total: self.files as usize,
+            files_avail: self.files_free as usize,
+            free: bytes(self.blocks_free),
+            avail: bytes(self.blocks_avail),
+            total: bytes(self.blocks),
+            name_max: 255,
+            fs_type: fs_type.to_string(),
+            fs_mounted_from: source.to_string(),
+            fs_mounted_on: target.to_string(),
+        }
+    }
+}
+
+// The types of the fields of `statvfs` depend on the platform
+#[allow(clippy::unnecessary_cast)]
+fn statvfs(path: &Path) -> io::Result<MountUsage> {
+    let path = CString::new(path.as_os_str().as_encoded_bytes())?;
+    let mut info: libc::statvfs = unsafe { mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut info) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(MountUsage {
+        block_size: info.f_bsize as u64,
+        blocks: info.f_blocks as u64,
+        blocks_free: info.f_bfree as u64,
+        blocks_avail: info.f_bavail as u64,
+        files: info.f_files as u64,
+        files_free: info.f_ffree as u64,
+    })
+}
+
+fn invalid_data(path: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("Could not parse {path}"))
+}
+
+// Copied and adapted from https://docs.rs/users/0.11.0/src/users/base.rs.html#326-360
+// Copyright (c) 2019 Benjamin Sago, MIT License
+pub fn get_username_by_uid(uid: uid_t) -> Option<CString> {
+    let mut passwd = unsafe { mem::zeroed::<c_passwd>() };
+    let mut buf = vec![0; 2048];
+    let mut result = ptr::null_mut::<c_passwd>();
+
+    loop {
+        let r =
+            unsafe { libc::getpwuid_r(uid, &mut passwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+
+        if r != libc::ERANGE {
+            break;
+        }
+
+        let newsize = buf.len().checked_mul(2)?;
+        buf.resize(newsize, 0);
+    }
+
+    if result.is_null() {
+        // There is no such user, or an error has occurred.
+        // errno gets set if there’s an error.
+        return None;
+    }
+
+    if result != &mut passwd {
+        // The result of getpwuid_r should be its input passwd.
+        return None;
+    }
+    let ptr = unsafe { result.read() }.pw_name;
+    let name = unsafe { CStr::from_ptr(ptr) };
+    Some(CString::from(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixture_sysroot() {
+        let sysroot = SysRoot::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/sysroot"
+        ));
+        assert!(!sysroot.is_live());
+        assert_eq!(
+            sysroot.path("/proc/loadavg"),
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sysroot/proc/loadavg")
+        );
+        assert_eq!(sysroot.path("cg_stats.toml"), Path::new("cg_stats.toml"));
+
+        let load = sysroot.load_average().unwrap();
+        assert_eq!((load.one, load.five, load.fifteen), (0.52, 1.25, 3.8));
+        assert_eq!(sysroot.uptime().unwrap(), Duration::from_secs(273784));
+        assert_eq!(sysroot.cpus().unwrap(), 4);
+        assert_eq!(sysroot.user_name(1000).as_deref(), Some("alice"));
+        assert_eq!(sysroot.user_name(1001), None);
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        assert_eq!(
+            sysroot.meminfo().unwrap().get("MemAvailable"),
+            Some(&ByteSize::kib(6000000))
+        );
+        assert!(sysroot
+            .read_to_string("/missing")
+            .unwrap_err()
+            .to_string()
+            .contains("/missing"));
+    }
+}
@@ -0,0 +1,178 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::json;
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::when::hostname;
+use crate::constants::INDENT_WIDTH;
+
+/// How to report the time spent by each component, with `--timings`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum TimingsFormat {
+    /// A table sorted by the slowest component, meant to be read by humans
+    Table,
+    /// A single line of JSON, meant to be appended to logs
+    Json,
+}
+
+/// The time spent by a component in each phase
+/// The collect phase is where commands are run and daemons and servers are queried,
+/// all components are collected concurrently
+#[derive(Serialize, Debug, Default)]
+pub struct Timing {
+    /// The position of the component in the configuration, starting at 1
+    pub position: usize,
+    pub component: &'static str,
+    #[serde(rename = "prepare_ms", serialize_with = "serialize_ms")]
+    pub prepare: Duration,
+    /// Not set for components read from the cache
+    #[serde(rename = "collect_ms", serialize_with = "serialize_optional_ms")]
+    pub collect: Option<Duration>,
+    #[serde(rename = "print_ms", serialize_with = "serialize_ms")]
+    pub print: Duration,
+    pub cached: bool,
+    /// Whether the component failed (including timing out)
+    pub error: bool,
+}
+
+impl Timing {
+    pub fn total(&self) -> Duration {
+        self.prepare + self.collect.unwrap_or_default() + self.print
+    }
+}
+
+fn ms(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 10000.0).round() / 10.0
+}
+
+fn serialize_ms<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(ms(*duration))
+}
+
+fn serialize_optional_ms<S: serde::Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serialize_ms(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn format_ms(duration: Duration) -> String {
+    format!("{:.1}ms", ms(duration))
+}
+
+/// Write the timings of all the components, the slowest first
+/// `total` is the time taken by the whole run, which is less than the sum of the components
+/// because they are collected concurrently
+pub fn write_timings(
+    out: &mut dyn Write,
+    format: TimingsFormat,
+    mut timings: Vec<Timing>,
+    total: Duration,
+) -> io::Result<()> {
+    timings.sort_by_key(|timing| std::cmp::Reverse(timing.total()));
+    match format {
+        TimingsFormat::Json => {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let report = json!({
+                "timestamp": timestamp,
+                "hostname": hostname(),
+                "total_ms": ms(total),
+                "components": timings,
+            });
+            writeln!(out, "{report}")
+        }
+        TimingsFormat::Table => {
+            let indent = " ".repeat(INDENT_WIDTH);
+            let name_width = timings
+                .iter()
+                .map(|timing| timing.component.len())
+                .chain(["component".len()])
+                .max()
+                .unwrap_or_default();
+            writeln!(out, "Timings (total {}):", format_ms(total))?;
+            writeln!(
+                out,
+                "{indent} # {:<name_width$} {:>9} {:>9} {:>9} {:>9}",
+                "component", "prepare", "collect", "print", "total"
+            )?;
+            for timing in timings {
+                let collect = timing.collect.map_or("-".to_string(), format_ms);
+                let note = match (timing.cached, timing.error) {
+                    (true, _) => "  cached",
+                    (false, true) => "  error",
+                    (false, false) => "",
+                };
+                writeln!(
+                    out,
+                    "{indent}{:>2} {:<name_width$} {:>9} {collect:>9} {:>9} {:>9}{note}",
+                    timing.position,
+                    timing.component,
+                    format_ms(timing.prepare),
+                    format_ms(timing.print),
+                    format_ms(timing.total()),
+                )?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_timings() {
+        let timings = vec![
+            Timing {
+                position: 1,
+                component: "uptime",
+                prepare: Duration::from_micros(20),
+                collect: Some(Duration::from_micros(150)),
+                print: Duration::from_micros(30),
+                ..Default::default()
+            },
+            Timing {
+                position: 2,
+                component: "weather",
+                collect: Some(Duration::from_millis(812)),
+                print: Duration::from_micros(100),
+                error: true,
+                ..Default::default()
+            },
+            Timing {
+                position: 3,
+                component: "docker",
+                print: Duration::from_micros(1250),
+                cached: true,
+                ..Default::default()
+            },
+        ];
+        let mut out = vec![];
+        write_timings(
+            &mut out,
+            TimingsFormat::Table,
+            timings,
+            Duration::from_millis(815),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Timings (total 815.0ms):\n   \
+             # component   prepare   collect     print     total\n   \
+             2 weather       0.0ms   812.0ms     0.1ms   812.1ms  error\n   \
+             3 docker        0.0ms         -     1.3ms     1.3ms  cached\n   \
+             1 uptime        0.0ms     0.1ms     0.0ms     0.2ms\n"
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! Golden tests: every configuration of `tests/golden` is rendered against the fixtures of
+//! `tests/fixtures` (a fake system root, fake `systemctl` and `fail2ban-client`,
+//! fake Docker and HTTP servers, and the state of a previous run of `cg-stats`),
+//! and the output is compared to the `.txt` file next to it
+//!
+//! Run with `UPDATE_GOLDEN=1` to write the current output to the `.txt` files instead
+
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+const GOLDEN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+
+/// The containers known to the fake Docker daemon
+const CONTAINERS: &str = r#"[
+    { "Id": "1", "Names": ["/nginx"], "State": "running", "Status": "Up 3 hours" },
+    { "Id": "2", "Names": ["/postgres"], "State": "exited", "Status": "Exited (0) 2 days ago" }
+]"#;
+
+/// The containers of the Compose stack, which the fake Docker daemon returns for filtered lists
+const COMPOSE_CONTAINERS: &str = r#"[
+    {
+        "Id": "3", "Names": ["/website-web-1"], "State": "running", "Status": "Up 5 minutes",
+        "Labels": { "com.docker.compose.service": "web" }
+    },
+    {
+        "Id": "4", "Names": ["/website-worker-1"], "State": "restarting", "Status": "Restarting (1) 10 seconds ago",
+        "Labels": { "com.docker.compose.service": "worker" }
+    }
+]"#;
+
+/// The CPU usage of the cgroups of the fixtures in the state of a previous run of `cg-stats`,
+/// 100 seconds before the current one
+const CG_STATS_STATE: &str = r#"
+[user.alice]
+usage_usec = 7654321
+
+[system.nginx]
+usage_usec = 1234567
+"#;
+
+/// The reply of the fake weather service
+const WEATHER: &str = "Toronto: ⛅️  +12°C\n";
+
+/// Read an HTTP request and return its request line (like `GET /containers/json HTTP/1.1`)
+fn read_request(stream: impl Read) -> String {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    let mut line = String::new();
+    while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+        line.clear();
+    }
+    request_line
+}
+
+fn write_response(mut stream: impl Write, content_type: &str, body: &str) {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+        Connection: close\r\n\r\n{body}",
+        body.len()
+    )
+    .unwrap();
+}
+
+/// Answer the requests for the list of containers on `docker.sock` in `dir`
+fn serve_docker(dir: &Path) {
+    let listener = UnixListener::bind(dir.join("docker.sock")).unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            let request = read_request(&stream);
+            let body = match request.contains("filters") {
+                true => COMPOSE_CONTAINERS,
+                false => CONTAINERS,
+            };
+            write_response(&stream, "application/json", body);
+        }
+    });
+}
+
+/// Answer all requests with the weather, returns the URL of the server
+fn serve_weather() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            read_request(&stream);
+            write_response(&stream, "text/plain; charset=utf-8", WEATHER);
+        }
+    });
+    url
+}
+
+/// Write the state of a previous run of `cg-stats` to `cg_stats.toml` in `dir`,
+/// so that it prints the usage since then
+fn seed_cg_stats(dir: &Path) {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap() - Duration::from_secs(100);
+    let state = format!(
+        "time = {{ secs_since_epoch = {}, nanos_since_epoch = {} }}\n{CG_STATS_STATE}",
+        time.as_secs(),
+        time.subsec_nanos()
+    );
+    fs::write(dir.join("cg_stats.toml"), state).unwrap();
+}
+
+fn configs() -> Vec<PathBuf> {
+    let mut configs: Vec<_> = fs::read_dir(GOLDEN)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "kdl"))
+        .collect();
+    configs.sort();
+    configs
+}
+
+#[test]
+fn test_golden() {
+    let dir = env::temp_dir().join(format!("rust-motd-golden-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    serve_docker(&dir);
+    seed_cg_stats(&dir);
+    let weather_url = serve_weather();
+    let path = env::join_paths(
+        [PathBuf::from(FIXTURES).join("bin")]
+            .into_iter()
+            .chain(env::split_paths(&env::var_os("PATH").unwrap_or_default())),
+    )
+    .unwrap();
+
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    let mut mismatches = vec![];
+    for config in configs() {
+        let output = Command::new(env!("CARGO_BIN_EXE_rust-motd"))
+            .arg(&config)
+            .arg("--sysroot")
+            .arg(Path::new(FIXTURES).join("sysroot"))
+            .env("PATH", &path)
+            .env("TZ", "UTC")
+            .env("GOLDEN_DIR", &dir)
+            .env("GOLDEN_HTTP", &weather_url)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let golden = config.with_extension("txt");
+        if update {
+            fs::write(&golden, &stdout).unwrap();
+        } else if fs::read_to_string(&golden).ok().as_deref() != Some(&*stdout) {
+            eprintln!("{}:\n{stdout}", golden.display());
+            mismatches.push(golden);
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(
+        mismatches.is_empty(),
+        "The output differs from {mismatches:?} (run with UPDATE_GOLDEN=1 to update them)"
+    );
+}